@@ -0,0 +1,93 @@
+//! 比较多线程下载时各写入方式 (`--writer`) 的吞吐量
+//!
+//! 模拟多个线程交错推送数据块, 每轮写入一个完整文件并 flush。
+//! 运行: `cargo bench --bench writers`, 测试文件写在 `FD_BENCH_DIR` (默认为系统临时目录) 下。
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/pusher/mod.rs"]
+mod pusher;
+
+use bytes::Bytes;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+#[cfg(target_pointer_width = "64")]
+use fast_pull::file::RandFilePusherMmap;
+use fast_pull::{ProgressEntry, RandPusher};
+use pusher::{FileWriter, RandFilePusherDirect, RandFilePusherPwrite};
+use std::{env, fmt::Debug, fs, path::Path};
+use tokio::runtime::Runtime;
+
+const FILE_SIZE: u64 = 64 * 1024 * 1024;
+const CHUNK_SIZE: u64 = 64 * 1024;
+const THREADS: u64 = 8;
+const BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+/// 把文件分成 `THREADS` 段, 按轮流的顺序生成各段的数据块, 与多线程下载的到达顺序相近
+fn chunks() -> Vec<(ProgressEntry, Bytes)> {
+    let data = Bytes::from(vec![0x5a; CHUNK_SIZE as usize]);
+    let segment = FILE_SIZE / THREADS;
+    let mut chunks = Vec::with_capacity((FILE_SIZE / CHUNK_SIZE) as usize);
+    for offset in (0..segment).step_by(CHUNK_SIZE as usize) {
+        for thread in 0..THREADS {
+            let start = thread * segment + offset;
+            chunks.push((start..start + CHUNK_SIZE, data.clone()));
+        }
+    }
+    chunks
+}
+
+async fn push_all<P: RandPusher<Error: Debug>>(mut pusher: P, chunks: &[(ProgressEntry, Bytes)]) {
+    for (range, content) in chunks {
+        pusher.push(range.clone(), content.clone()).await.unwrap();
+    }
+    pusher.flush().await.unwrap();
+}
+
+async fn write_file(writer: FileWriter, path: &Path, chunks: &[(ProgressEntry, Bytes)]) {
+    let _ = fs::remove_file(path);
+    match writer {
+        #[cfg(target_pointer_width = "64")]
+        FileWriter::Mmap => {
+            let pusher = RandFilePusherMmap::new(path, FILE_SIZE, BUFFER_SIZE)
+                .await
+                .unwrap();
+            push_all(pusher, chunks).await
+        }
+        FileWriter::Pwrite => {
+            let pusher = RandFilePusherPwrite::new(path, BUFFER_SIZE).await.unwrap();
+            push_all(pusher, chunks).await
+        }
+        FileWriter::Direct => {
+            let pusher = RandFilePusherDirect::new(path, FILE_SIZE, BUFFER_SIZE)
+                .await
+                .unwrap();
+            push_all(pusher, chunks).await
+        }
+    }
+}
+
+fn bench_writers(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let dir = env::var_os("FD_BENCH_DIR").map_or_else(env::temp_dir, Into::into);
+    let chunks = chunks();
+    let mut group = c.benchmark_group("writer");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(FILE_SIZE));
+    for writer in [
+        #[cfg(target_pointer_width = "64")]
+        FileWriter::Mmap,
+        FileWriter::Pwrite,
+        FileWriter::Direct,
+    ] {
+        let path = dir.join(format!("fast-down-bench-{writer:?}.bin"));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{writer:?}")),
+            &writer,
+            |b, &writer| b.iter(|| runtime.block_on(write_file(writer, &path, &chunks))),
+        );
+        let _ = fs::remove_file(&path);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_writers);
+criterion_main!(benches);
@@ -1,10 +1,20 @@
-use clap::{Parser, Subcommand};
-use color_eyre::Result;
+use crate::{
+    auth::Credentials,
+    dedupe::Dedupe,
+    fmt,
+    persist::{self, DatabaseEntry, SavedOptions},
+    pusher::{FileAllocation, FileWriter},
+    redact::{self, Redactor},
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::{Result, eyre::eyre};
 use config::{Config, Environment, File};
 use crossterm::terminal;
-use reqwest::header::{HeaderMap, HeaderName};
+use glob::Pattern;
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
 use std::path::{Path, PathBuf};
-use std::{env, str::FromStr, time::Duration};
+use std::{env, str::FromStr, sync::OnceLock, time::Duration};
+use url::Url;
 
 /// 超级快的下载器
 #[derive(Parser, Debug)]
@@ -13,6 +23,14 @@ use std::{env, str::FromStr, time::Duration};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// 下载记录的保存目录
+    #[arg(long, global = true, value_name = "DIR", env = "FD_STATE_DIR")]
+    state_dir: Option<PathBuf>,
+
+    /// 额外读取的配置文件, 优先级高于自动发现的配置文件
+    #[arg(long, global = true, value_name = "FILE", env = "FD_CONFIG")]
+    config: Option<PathBuf>,
 }
 
 #[derive(Parser, Debug)]
@@ -21,6 +39,14 @@ struct Cli {
 struct CliDefault {
     #[command(flatten)]
     cmd: DownloadCli,
+
+    /// 下载记录的保存目录
+    #[arg(long, value_name = "DIR", env = "FD_STATE_DIR")]
+    state_dir: Option<PathBuf>,
+
+    /// 额外读取的配置文件, 优先级高于自动发现的配置文件
+    #[arg(long, value_name = "FILE", env = "FD_CONFIG")]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -28,12 +54,38 @@ struct CliDefault {
 enum Commands {
     /// 下载文件 (默认)
     Download(DownloadCli),
-    /// 清除已下载完成的链接
-    Clean,
+    /// 管理下载缓存
+    Cache(CacheCli),
+    /// 清除下载记录 (默认清除已下载完成的链接)
+    Clean(CleanCli),
+    /// 查看配置
+    Config(ConfigCli),
+    /// 显示下载历史
+    History(HistoryCli),
     // /// 更新 fast-down
     // Update,
-    /// 显示数据库
-    List,
+    /// 显示下载记录
+    List(ListCli),
+    /// 递归下载目录索引页中的所有文件
+    Mirror(MirrorCli),
+    /// 继续未完成的下载
+    Resume(ResumeCli),
+    /// 按主机和日期统计下载历史
+    Stats(StatsCli),
+}
+
+#[derive(clap::Args, Debug)]
+struct MirrorCli {
+    /// 只下载匹配的文件 (glob, 相对于根目录, 可多次使用)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// 跳过匹配的文件 (glob, 相对于根目录, 可多次使用)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    #[command(flatten)]
+    download: DownloadCli,
 }
 
 #[derive(clap::Args, Debug)]
@@ -42,6 +94,153 @@ struct DownloadCli {
     #[arg(required = true)]
     url: String,
 
+    #[command(flatten)]
+    options: DownloadOptions,
+}
+
+#[derive(clap::Args, Debug)]
+struct CacheCli {
+    /// 缓存目录 (默认使用配置文件中的 cache_dir)
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: CacheCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommands {
+    /// 列出缓存的文件
+    Ls {
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+    },
+    /// 清理缓存 (默认按配置中的 cache_max_size 删除最久未使用的文件)
+    Prune {
+        /// 缓存的总大小上限 (如 512M, 10G)
+        #[arg(long, value_name = "SIZE", value_parser = fmt::parse_size)]
+        max_size: Option<u64>,
+
+        /// 删除超过该时长未使用的文件 (如 12h, 7d, 2w)
+        #[arg(long, value_name = "AGE", value_parser = fmt::parse_duration)]
+        older_than: Option<Duration>,
+
+        /// 删除全部缓存
+        #[arg(long, conflicts_with_all = ["max_size", "older_than"])]
+        all: bool,
+    },
+    /// 显示缓存占用的空间
+    Size,
+}
+
+#[derive(clap::Args, Debug)]
+struct CleanCli {
+    /// 只清除文件超过该时长未修改的记录 (如 30m, 12h, 7d, 2w), 按下载文件或临时文件的修改时间判断,
+    /// 文件不存在的记录视为已过期
+    #[arg(long, value_name = "AGE", value_parser = fmt::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// 只清除路径或链接匹配该 glob 的记录
+    #[arg(long, value_name = "GLOB")]
+    pattern: Option<String>,
+
+    /// 只清除文件已不存在的记录
+    #[arg(long)]
+    missing: bool,
+
+    /// 同时删除未完成下载的临时文件
+    #[arg(long)]
+    delete_files: bool,
+
+    /// 只列出将被清除的记录, 不做修改
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ListCli {
+    /// 只显示路径或链接中包含该字符串的记录
+    filter: Option<String>,
+
+    /// 以 JSON 格式输出
+    #[arg(long)]
+    json: bool,
+
+    /// 只显示未完成的下载
+    #[arg(long, conflicts_with = "complete")]
+    incomplete: bool,
+
+    /// 只显示已完成的下载
+    #[arg(long)]
+    complete: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ConfigCli {
+    #[command(subcommand)]
+    command: ConfigCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// 显示合并后的配置及每个值的来源
+    Show {
+        /// 叠加指定的配置方案
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// 叠加按此链接的主机名自动选择的配置方案
+        #[arg(long, conflicts_with = "profile")]
+        url: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct HistoryCli {
+    /// 只显示路径或链接中包含该字符串的记录
+    filter: Option<String>,
+
+    /// 最多显示的记录数
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+
+    /// 以 JSON 格式输出
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct StatsCli {
+    /// 按日统计时显示的天数
+    #[arg(long, default_value_t = 14)]
+    days: u64,
+
+    /// 以 JSON 格式输出
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ResumeCli {
+    /// 要继续下载的文件路径
+    #[arg(required_unless_present = "all")]
+    paths: Vec<PathBuf>,
+
+    /// 继续所有未完成的下载
+    #[arg(long, conflicts_with = "paths")]
+    all: bool,
+
+    /// 使用新的链接继续下载, 用于刷新过期的签名链接 (只能指定一个文件)
+    #[arg(long, conflicts_with = "all")]
+    url: Option<String>,
+
+    #[command(flatten)]
+    options: DownloadOptions,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct DownloadOptions {
     /// 强制覆盖已有文件
     #[arg(short, long = "allow-overwrite")]
     force: bool,
@@ -58,6 +257,38 @@ struct DownloadCli {
     #[arg(long = "no-continue")]
     no_resume: bool,
 
+    /// 远端文件未改变时跳过下载, 否则覆盖已有文件
+    #[arg(short = 'N', long)]
+    timestamping: bool,
+
+    /// 不检查远端文件是否改变
+    #[arg(long, overrides_with = "timestamping")]
+    no_timestamping: bool,
+
+    /// 已有同一链接的完整下载 (ETag 和大小相同) 时复用该文件而不重新下载
+    #[arg(long, value_name = "MODE")]
+    dedupe: Option<Dedupe>,
+
+    /// 复用文件前校验 SHA-256, 并在下载完成后计算 SHA-256
+    #[arg(long)]
+    dedupe_verify: bool,
+
+    /// 不校验也不计算 SHA-256
+    #[arg(long, overrides_with = "dedupe_verify")]
+    no_dedupe_verify: bool,
+
+    /// 缓存目录, 设置后完整的下载会存入缓存, 远端未改变时直接从缓存复制
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// 缓存的总大小上限 (如 512M, 10G), 超出时删除最久未使用的文件
+    #[arg(long, value_name = "SIZE", value_parser = fmt::parse_size)]
+    cache_max_size: Option<u64>,
+
+    /// 使用配置文件中的方案 [profile.<NAME>], 未指定时按链接的主机名自动选择
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
     /// 保存目录
     #[arg(short = 'd', long = "dir")]
     save_folder: Option<String>,
@@ -66,10 +297,14 @@ struct DownloadCli {
     #[arg(short, long)]
     threads: Option<usize>,
 
-    /// 自定义文件名
+    /// 自定义文件名 (为 - 时输出到 stdout)
     #[arg(short = 'o', long = "out")]
     file_name: Option<String>,
 
+    /// 下载中临时文件的后缀 (为空时直接写入目标文件)
+    #[arg(long)]
+    part_suffix: Option<String>,
+
     /// 代理地址 (格式: http://proxy:port 或 socks5://proxy:port)
     #[arg(short, long = "all-proxy")]
     proxy: Option<String>,
@@ -78,6 +313,40 @@ struct DownloadCli {
     #[arg(short = 'H', long = "header", value_name = "Key: Value")]
     headers: Vec<String>,
 
+    /// 用户名和密码, 也可通过环境变量 FD_USER 设置
+    #[arg(
+        short,
+        long,
+        value_name = "USER:PASSWORD",
+        env = "FD_USER",
+        hide_env_values = true
+    )]
+    user: Option<String>,
+
+    /// Bearer 令牌, 也可通过环境变量 FD_BEARER 设置
+    #[arg(long, value_name = "TOKEN", env = "FD_BEARER", hide_env_values = true)]
+    bearer: Option<String>,
+
+    /// 不从 .netrc 读取凭据
+    #[arg(long)]
+    no_netrc: bool,
+
+    /// 从 Netscape 格式的 cookies.txt 加载 Cookie
+    #[arg(long, value_name = "FILE")]
+    load_cookies: Option<PathBuf>,
+
+    /// 下载结束后将 Cookie 保存为 Netscape 格式
+    #[arg(long, value_name = "FILE")]
+    save_cookies: Option<PathBuf>,
+
+    /// 文件空间分配方式 (mmap 写入方式不支持 none)
+    #[arg(long, value_enum)]
+    file_allocation: Option<FileAllocation>,
+
+    /// 多线程下载时的写入方式
+    #[arg(long, value_enum)]
+    writer: Option<FileWriter>,
+
     /// 写入缓冲区大小 (单位: B)
     #[arg(long)]
     write_buffer_size: Option<usize>,
@@ -118,6 +387,10 @@ struct DownloadCli {
     #[arg(short, long)]
     verbose: bool,
 
+    /// 输出链接时额外隐藏的查询参数 (可多次使用)
+    #[arg(long, value_name = "NAME")]
+    redact_param: Vec<String>,
+
     /// 开启多路复用
     #[arg(long)]
     multiplexing: bool,
@@ -135,21 +408,141 @@ struct DownloadCli {
 #[allow(clippy::large_enum_variant)]
 pub enum Args {
     Download(DownloadArgs),
+    Cache(CacheArgs),
     // Update,
-    Clean,
-    List,
+    Clean(CleanArgs),
+    Config(ConfigArgs),
+    History(HistoryArgs),
+    List(ListArgs),
+    Mirror(MirrorArgs),
+    Resume(ResumeArgs),
+    Stats(StatsArgs),
+}
+
+#[derive(Debug)]
+pub struct MirrorArgs {
+    pub download: DownloadArgs,
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+pub struct CacheArgs {
+    pub dir: PathBuf,
+    pub action: CacheAction,
+}
+
+#[derive(Debug)]
+pub enum CacheAction {
+    Ls {
+        json: bool,
+    },
+    Prune {
+        max_size: Option<u64>,
+        older_than: Option<Duration>,
+        all: bool,
+    },
+    Size,
+}
+
+#[derive(Debug)]
+pub struct CleanArgs {
+    pub older_than: Option<Duration>,
+    pub pattern: Option<Pattern>,
+    pub missing: bool,
+    pub delete_files: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub struct ListArgs {
+    pub filter: Option<String>,
+    pub redactor: Redactor,
+    pub json: bool,
+    pub incomplete: bool,
+    pub complete: bool,
+}
+
+#[derive(Debug)]
+pub struct ConfigArgs {
+    /// 按优先级从低到高排列的配置文件及其是否存在
+    pub files: Vec<(PathBuf, bool)>,
+    pub config: Config,
+    /// 叠加的配置方案
+    pub profile: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct HistoryArgs {
+    pub filter: Option<String>,
+    pub limit: usize,
+    pub json: bool,
+}
+
+#[derive(Debug)]
+pub struct StatsArgs {
+    pub days: u64,
+    pub json: bool,
+}
+
+#[derive(Debug)]
+pub struct ResumeArgs {
+    pub paths: Vec<PathBuf>,
+    pub all: bool,
+    pub url: Option<String>,
+    pub download: DownloadArgs,
+    options: DownloadOptions,
+}
+
+impl ResumeArgs {
+    fn new(cli: ResumeCli) -> Result<Self> {
+        Ok(Self {
+            paths: cli.paths,
+            all: cli.all,
+            url: cli.url.clone(),
+            download: DownloadArgs::new(cli.url.unwrap_or_default(), cli.options.clone(), None)?,
+            options: cli.options,
+        })
+    }
+
+    /// 以记录中保存的选项为基础生成下载参数, 命令行参数优先
+    ///
+    /// 返回的链接是记录中的原链接, 使用 `--url` 时由调用方替换
+    pub fn entry_args(&self, entry: &DatabaseEntry) -> Result<DownloadArgs> {
+        // 用原链接的主机名选择配置方案, 与最初下载时一致
+        DownloadArgs::new(
+            entry.url.clone(),
+            self.options.clone(),
+            entry.options.as_ref(),
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DownloadArgs {
     pub url: String,
+    /// 使用的配置方案
+    pub profile: Option<String>,
     pub force: bool,
     pub resume: bool,
+    /// 只继续已有的下载, 无法继续或大小、ETag、Last-Modified 与记录矛盾时直接失败
+    pub strict_resume: bool,
+    pub timestamping: bool,
+    pub dedupe: Dedupe,
+    pub dedupe_verify: bool,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_max_size: Option<u64>,
     pub save_folder: PathBuf,
     pub threads: usize,
     pub file_name: Option<String>,
+    pub part_suffix: String,
     pub proxy: Option<String>,
     pub headers: HeaderMap,
+    pub credentials: Credentials,
+    pub load_cookies: Option<PathBuf>,
+    pub save_cookies: Option<PathBuf>,
+    pub file_allocation: FileAllocation,
+    pub writer: FileWriter,
     pub write_buffer_size: usize,
     pub write_queue_cap: usize,
     pub repaint_gap: Duration,
@@ -159,184 +552,733 @@ pub struct DownloadArgs {
     pub yes: bool,
     pub no: bool,
     pub verbose: bool,
+    pub redactor: Redactor,
     pub multiplexing: bool,
     pub accept_invalid_certs: bool,
     pub accept_invalid_hostnames: bool,
 }
 
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// 系统级配置文件
+fn system_config_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("PROGRAMDATA").map(PathBuf::from)
+    } else {
+        Some(PathBuf::from("/etc"))
+    };
+    base.map(|base| base.join("fast-down").join("config.toml"))
+}
+
+/// 用户级配置文件
+fn user_config_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| env::home_dir().map(|home| home.join(".config")))
+    };
+    base.map(|base| base.join("fast-down").join("config.toml"))
+}
+
+/// 按优先级从低到高排列的配置文件:
+/// 可执行文件旁的 config.toml (随程序分发的默认配置)、系统配置、用户配置、
+/// 当前目录的 fast-down.toml, 最后是 `--config` 指定的文件
+fn config_files() -> Vec<PathBuf> {
+    let exe_config_path = env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.join("config.toml")));
+    [
+        exe_config_path,
+        system_config_path(),
+        user_config_path(),
+        Some(PathBuf::from("fast-down.toml")),
+        CONFIG_PATH.get().cloned(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// 按顺序合并所有配置文件, 不包含环境变量
+fn load_config_files() -> Result<Config> {
+    let mut config = Config::builder();
+    for path in config_files() {
+        // 显式指定的配置文件必须存在
+        let required = CONFIG_PATH.get() == Some(&path);
+        config = config.add_source(File::from(path).required(required));
+    }
+    Ok(config.build()?)
+}
+
+/// 按顺序合并所有配置文件, 最后读取 FD_ 开头的环境变量
+fn load_config() -> Result<Config> {
+    Ok(Config::builder()
+        .add_source(load_config_files()?)
+        .add_source(Environment::with_prefix("FD"))
+        .build()?)
+}
+
+/// 默认隐藏的查询参数加上配置文件中的 `redact_params`
+fn config_redactor(config: &Config) -> Redactor {
+    let mut redactor = Redactor::default();
+    if let Ok(value) = config.get::<Vec<String>>("General.redact_params") {
+        redactor.params.extend(value);
+    }
+    redactor
+}
+
+/// 按主机名选择配置方案, 多个方案匹配时使用最长 (最具体) 的模式, 相同时按名称排序
+fn match_profile<'a>(profiles: &'a [(String, Vec<Pattern>)], host: &str) -> Option<&'a str> {
+    profiles
+        .iter()
+        .filter_map(|(name, patterns)| {
+            patterns
+                .iter()
+                .filter(|p| p.matches(host))
+                .map(|p| p.as_str().len())
+                .max()
+                .map(|len| (len, name))
+        })
+        .min_by(|(a_len, a_name), (b_len, b_name)| b_len.cmp(a_len).then(a_name.cmp(b_name)))
+        .map(|(_, name)| name.as_str())
+}
+
+/// 读取配置文件中各个方案的 `match` 模式, 可以是字符串或字符串数组
+fn profile_patterns(config: &Config) -> Result<Vec<(String, Vec<Pattern>)>> {
+    let Ok(profiles) = config.get_table("profile") else {
+        return Ok(vec![]);
+    };
+    let mut result = Vec::new();
+    for name in profiles.into_keys() {
+        let key = format!("profile.{name}.match");
+        let patterns = match config.get_string(&key) {
+            Ok(pattern) => vec![pattern],
+            Err(_) => config.get::<Vec<String>>(&key).unwrap_or_default(),
+        };
+        let patterns = patterns
+            .iter()
+            .map(|p| Pattern::new(&p.to_ascii_lowercase()))
+            .collect::<Result<_, _>>()?;
+        result.push((name, patterns));
+    }
+    Ok(result)
+}
+
+/// 读取配置并叠加配置方案 `[profile.<name>]` 中的 General 和 Headers
+///
+/// 优先使用 `profile` 指定的方案, 否则按链接的主机名自动选择。
+/// 方案覆盖所有配置文件中的值, FD_ 开头的环境变量仍然优先于方案
+fn load_profile_config(url: &str, profile: Option<&str>) -> Result<(Config, Option<String>)> {
+    let files = load_config_files()?;
+    let config = Config::builder()
+        .add_source(files.clone())
+        .add_source(Environment::with_prefix("FD"))
+        .build()?;
+    let name = match profile {
+        Some(name) => name.to_string(),
+        None => {
+            let host = Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(|h| h.to_ascii_lowercase()));
+            let profiles = profile_patterns(&config)?;
+            match host.and_then(|host| match_profile(&profiles, &host).map(String::from)) {
+                Some(name) => name,
+                None => return Ok((config, None)),
+            }
+        }
+    };
+    let profile = config
+        .get_table(&format!("profile.{name}"))
+        .map_err(|_| eyre!("{}", t!("err.profile-not-found", name = name)))?;
+    let mut overlay = Config::builder();
+    for section in ["General", "Headers"] {
+        let Some(table) = profile.get(section) else {
+            continue;
+        };
+        for (key, value) in table.clone().into_table()? {
+            overlay = overlay.set_override(format!("{section}.{key}"), value)?;
+        }
+    }
+    let config = Config::builder()
+        .add_source(files)
+        .add_source(overlay.build()?)
+        .add_source(Environment::with_prefix("FD"))
+        .build()?;
+    Ok((config, Some(name)))
+}
+
+impl DownloadArgs {
+    fn new(url: String, cli: DownloadOptions, saved: Option<&SavedOptions>) -> Result<Self> {
+        let mut args = DownloadArgs {
+            url,
+            profile: None,
+            force: false,
+            resume: false,
+            strict_resume: false,
+            timestamping: false,
+            dedupe: Dedupe::default(),
+            dedupe_verify: false,
+            cache_dir: None,
+            cache_max_size: None,
+            save_folder: Path::new(".").to_path_buf(),
+            threads: 8,
+            file_name: cli.file_name,
+            part_suffix: ".fdpart".to_string(),
+            proxy: None,
+            headers: HeaderMap::new(),
+            credentials: Credentials {
+                netrc: true,
+                ..Default::default()
+            },
+            load_cookies: None,
+            save_cookies: None,
+            file_allocation: FileAllocation::default(),
+            writer: FileWriter::default(),
+            write_buffer_size: 8 * 1024 * 1024,
+            write_queue_cap: 10240,
+            progress_width: terminal::size()
+                .ok()
+                .and_then(|s| s.0.checked_sub(36))
+                .unwrap_or(50),
+            retry_gap: Duration::from_millis(500),
+            repaint_gap: Duration::from_millis(100),
+            browser: true,
+            yes: false,
+            no: false,
+            verbose: false,
+            redactor: Redactor::default(),
+            multiplexing: true,
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+        };
+        let (config, profile) = load_profile_config(&args.url, cli.profile.as_deref())?;
+        args.profile = profile;
+        if let Ok(value) = config.get_bool("General.force") {
+            args.force = value;
+        }
+        if let Ok(value) = config.get_bool("General.resume") {
+            args.resume = value;
+        }
+        if let Ok(value) = config.get_bool("General.timestamping") {
+            args.timestamping = value;
+        }
+        if let Ok(value) = config.get_string("General.dedupe") {
+            args.dedupe = Dedupe::from_str(&value, true).map_err(|e| eyre!(e))?;
+        }
+        if let Ok(value) = config.get_bool("General.dedupe_verify") {
+            args.dedupe_verify = value;
+        }
+        if let Ok(value) = config.get_string("General.cache_dir")
+            && !value.is_empty()
+        {
+            args.cache_dir = Some(value.into());
+        }
+        if let Ok(value) = config.get_string("General.cache_max_size") {
+            args.cache_max_size = Some(fmt::parse_size(&value).map_err(|e| eyre!(e))?);
+        }
+        if let Ok(value) = config.get_string("General.save_folder") {
+            args.save_folder = value.into();
+        }
+        if let Ok(value) = config.get_int("General.threads") {
+            args.threads = value.try_into()?;
+        }
+        if let Ok(value) = config.get_string("General.part_suffix") {
+            args.part_suffix = value;
+        }
+        if let Ok(value) = config.get_string("General.proxy")
+            && !value.is_empty()
+        {
+            args.proxy = Some(value);
+        }
+        if let Ok(value) = config.get_bool("General.netrc") {
+            args.credentials.netrc = value;
+        }
+        if let Ok(value) = config.get_string("General.load_cookies")
+            && !value.is_empty()
+        {
+            args.load_cookies = Some(value.into());
+        }
+        if let Ok(value) = config.get_string("General.save_cookies")
+            && !value.is_empty()
+        {
+            args.save_cookies = Some(value.into());
+        }
+        if let Ok(value) = config.get_string("General.file_allocation") {
+            args.file_allocation = FileAllocation::from_str(&value, true).map_err(|e| eyre!(e))?;
+        }
+        if let Ok(value) = config.get_string("General.writer") {
+            args.writer = FileWriter::from_str(&value, true).map_err(|e| eyre!(e))?;
+        }
+        if let Ok(value) = config.get_int("General.write_buffer_size") {
+            args.write_buffer_size = value.try_into()?;
+        }
+        if let Ok(value) = config.get_int("General.write_queue_cap") {
+            args.write_queue_cap = value.try_into()?;
+        }
+        if let Ok(value) = config.get_int("General.progress_width") {
+            args.progress_width = value.try_into()?;
+        }
+        if let Ok(value) = config.get_int("General.retry_gap") {
+            args.retry_gap = Duration::from_millis(value.try_into()?);
+        }
+        if let Ok(value) = config.get_int("General.repaint_gap") {
+            args.repaint_gap = Duration::from_millis(value.try_into()?);
+        }
+        if let Ok(value) = config.get_bool("General.browser") {
+            args.browser = value;
+        }
+        if let Ok(value) = config.get_bool("General.yes") {
+            args.yes = value;
+        }
+        if let Ok(value) = config.get_bool("General.no") {
+            args.no = value;
+        }
+        if let Ok(value) = config.get_bool("General.verbose") {
+            args.verbose = value;
+        }
+        args.redactor = config_redactor(&config);
+        if let Ok(value) = config.get_bool("General.multiplexing") {
+            args.multiplexing = value;
+        }
+        if let Ok(value) = config.get_bool("General.accept_invalid_hostnames") {
+            args.accept_invalid_hostnames = value;
+        }
+        if let Ok(value) = config.get_bool("General.accept_invalid_certs") {
+            args.accept_invalid_certs = value;
+        }
+        if let Ok(table) = config.get_table("Headers") {
+            for (key, value) in table {
+                let value_str = value.to_string();
+                match HeaderName::from_str(&key) {
+                    Ok(header_name) => match value_str.parse() {
+                        Ok(header_value) => {
+                            args.headers.insert(header_name, header_value);
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "无法解析请求头值\n请求头: {key}: {value_str}\n错误原因: {e:?}",
+                            );
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("无法解析请求头名称\n请求头: {key}\n错误原因: {e:?}",);
+                    }
+                }
+            }
+        }
+        if let Some(saved) = saved {
+            args.apply_saved(saved)?;
+        }
+        args.force = cli.force;
+        args.resume = cli.resume;
+        if cli.timestamping {
+            args.timestamping = true;
+        }
+        if cli.no_timestamping {
+            args.timestamping = false;
+        }
+        if let Some(value) = cli.dedupe {
+            args.dedupe = value;
+        }
+        if cli.dedupe_verify {
+            args.dedupe_verify = true;
+        }
+        if cli.no_dedupe_verify {
+            args.dedupe_verify = false;
+        }
+        if let Some(value) = cli.cache_dir {
+            args.cache_dir = Some(value);
+        }
+        if let Some(value) = cli.cache_max_size {
+            args.cache_max_size = Some(value);
+        }
+        if let Some(value) = cli.save_folder {
+            args.save_folder = value.into();
+        }
+        if let Some(value) = cli.threads {
+            args.threads = value;
+        }
+        if let Some(value) = cli.part_suffix {
+            args.part_suffix = value;
+        }
+        if let Some(value) = cli.proxy {
+            args.proxy.replace(value);
+        }
+        args.credentials.scope = Url::parse(&args.url).ok();
+        if let Some(value) = cli.user {
+            args.credentials.user = Some(value);
+        }
+        if let Some(value) = cli.bearer {
+            args.credentials.bearer = Some(value);
+        }
+        if cli.no_netrc {
+            args.credentials.netrc = false;
+        }
+        if let Some(value) = cli.load_cookies {
+            args.load_cookies = Some(value);
+        }
+        if let Some(value) = cli.save_cookies {
+            args.save_cookies = Some(value);
+        }
+        if let Some(value) = cli.file_allocation {
+            args.file_allocation = value;
+        }
+        if let Some(value) = cli.writer {
+            args.writer = value;
+        }
+        if let Some(value) = cli.write_buffer_size {
+            args.write_buffer_size = value;
+        }
+        if let Some(value) = cli.write_queue_cap {
+            args.write_queue_cap = value;
+        }
+        if let Some(value) = cli.progress_width {
+            args.progress_width = value;
+        }
+        if let Some(value) = cli.retry_gap {
+            args.retry_gap = Duration::from_millis(value);
+        }
+        if let Some(value) = cli.repaint_gap {
+            args.repaint_gap = Duration::from_millis(value);
+        }
+        if cli.browser {
+            args.browser = true;
+        }
+        args.yes = cli.yes;
+        args.no = cli.no;
+        args.verbose = cli.verbose;
+        args.redactor.params.extend(cli.redact_param);
+        if cli.multiplexing {
+            args.multiplexing = true;
+        }
+        if cli.accept_invalid_hostnames {
+            args.accept_invalid_hostnames = true;
+        }
+        if cli.accept_invalid_certs {
+            args.accept_invalid_certs = true;
+        }
+        for header in cli.headers {
+            let parts: Vec<_> = header.splitn(2, ':').map(|t| t.trim()).collect();
+            if parts.len() != 2 {
+                eprintln!("请求头格式错误: {header}");
+                continue;
+            }
+            args.headers
+                .insert(HeaderName::from_str(parts[0])?, parts[1].parse()?);
+        }
+        Ok(args)
+    }
+
+    /// 隐藏敏感请求头、链接中的签名和代理密码, 用于详细输出
+    pub fn redacted(&self) -> Self {
+        let mut args = self.clone();
+        args.url = self.redactor.url_str(&self.url);
+        args.headers = self.redactor.headers(&self.headers);
+        args.proxy = self.proxy.as_deref().map(|p| self.redactor.url_str(p));
+        args.credentials.scope = self
+            .credentials
+            .scope
+            .as_ref()
+            .map(|u| self.redactor.url(u));
+        args
+    }
+
+    /// 需要随下载记录保存的选项, 不包含凭据、Cookie 和代理密码
+    pub fn saved_options(&self) -> Result<SavedOptions> {
+        let headers = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !redact::is_sensitive_header(name))
+            // 浏览器模式下由链接重新生成
+            .filter(|(name, _)| {
+                !self.browser || (*name != header::ORIGIN && *name != header::REFERER)
+            })
+            .map(|(name, value)| {
+                let value = if name == header::REFERER {
+                    value
+                        .to_str()
+                        .map(|v| self.redactor.url_str(v).into_bytes())
+                        .unwrap_or_default()
+                } else {
+                    value.as_bytes().to_vec()
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        let proxy = self.proxy.as_deref().map(|p| match Url::parse(p) {
+            Ok(mut url) => {
+                let _ = url.set_password(None);
+                url.to_string()
+            }
+            Err(_) => p.to_string(),
+        });
+        Ok(SavedOptions {
+            headers,
+            proxy,
+            threads: self.threads as u64,
+            writer: value_name(&self.writer)?,
+            file_allocation: value_name(&self.file_allocation)?,
+            write_buffer_size: self.write_buffer_size as u64,
+            write_queue_cap: self.write_queue_cap as u64,
+            part_suffix: self.part_suffix.clone(),
+            load_cookies: self
+                .load_cookies
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+            save_cookies: self
+                .save_cookies
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+            browser: self.browser,
+            multiplexing: self.multiplexing,
+            accept_invalid_certs: self.accept_invalid_certs,
+            accept_invalid_hostnames: self.accept_invalid_hostnames,
+        })
+    }
+
+    /// 应用下载记录中保存的选项, 覆盖配置文件中的值
+    fn apply_saved(&mut self, saved: &SavedOptions) -> Result<()> {
+        for (name, value) in &saved.headers {
+            self.headers
+                .insert(HeaderName::from_str(name)?, HeaderValue::from_bytes(value)?);
+        }
+        // 保存的代理不含密码, 不能替换配置中带密码的代理
+        let has_password = self
+            .proxy
+            .as_deref()
+            .and_then(|p| Url::parse(p).ok())
+            .is_some_and(|url| url.password().is_some());
+        if let Some(proxy) = &saved.proxy
+            && !has_password
+        {
+            self.proxy = Some(proxy.clone());
+        }
+        self.threads = saved.threads.try_into()?;
+        self.writer = FileWriter::from_str(&saved.writer, true).map_err(|e| eyre!(e))?;
+        self.file_allocation =
+            FileAllocation::from_str(&saved.file_allocation, true).map_err(|e| eyre!(e))?;
+        self.write_buffer_size = saved.write_buffer_size.try_into()?;
+        self.write_queue_cap = saved.write_queue_cap.try_into()?;
+        self.part_suffix = saved.part_suffix.clone();
+        if let Some(path) = &saved.load_cookies {
+            self.load_cookies = Some(path.into());
+        }
+        if let Some(path) = &saved.save_cookies {
+            self.save_cookies = Some(path.into());
+        }
+        self.browser = saved.browser;
+        self.multiplexing = saved.multiplexing;
+        self.accept_invalid_certs = saved.accept_invalid_certs;
+        self.accept_invalid_hostnames = saved.accept_invalid_hostnames;
+        Ok(())
+    }
+}
+
+/// 命令行中使用的选项名称
+fn value_name(value: &(impl ValueEnum + std::fmt::Debug)) -> Result<String> {
+    let value = value
+        .to_possible_value()
+        .ok_or_else(|| eyre!("{value:?}"))?;
+    Ok(value.get_name().to_string())
+}
+
 impl Args {
     pub fn parse() -> Result<Args> {
         match Cli::try_parse().or_else(|err| match err.kind() {
             clap::error::ErrorKind::InvalidSubcommand | clap::error::ErrorKind::UnknownArgument => {
                 CliDefault::try_parse().map(|cli_default| Cli {
                     command: Commands::Download(cli_default.cmd),
+                    state_dir: cli_default.state_dir,
+                    config: cli_default.config,
                 })
             }
             _ => Err(err),
         }) {
-            Ok(cli) => match cli.command {
-                Commands::Download(cli) => {
-                    let mut args = DownloadArgs {
-                        url: cli.url,
-                        force: false,
-                        resume: false,
-                        save_folder: Path::new(".").to_path_buf(),
-                        threads: 8,
-                        file_name: cli.file_name,
-                        proxy: None,
-                        headers: HeaderMap::new(),
-                        write_buffer_size: 8 * 1024 * 1024,
-                        write_queue_cap: 10240,
-                        progress_width: terminal::size()
-                            .ok()
-                            .and_then(|s| s.0.checked_sub(36))
-                            .unwrap_or(50),
-                        retry_gap: Duration::from_millis(500),
-                        repaint_gap: Duration::from_millis(100),
-                        browser: true,
-                        yes: false,
-                        no: false,
-                        verbose: false,
-                        multiplexing: true,
-                        accept_invalid_certs: false,
-                        accept_invalid_hostnames: false,
-                    };
-                    let self_config_path = env::current_exe()
+            Ok(cli) => {
+                if let Some(dir) = cli.state_dir {
+                    persist::set_state_dir(dir);
+                }
+                if let Some(path) = cli.config {
+                    let _ = CONFIG_PATH.set(path);
+                }
+                Self::from_command(cli.command)
+            }
+            Err(err) => err.exit(),
+        }
+    }
+
+    fn from_command(command: Commands) -> Result<Args> {
+        match command {
+            Commands::Download(cli) => Ok(Args::Download(DownloadArgs::new(
+                cli.url,
+                cli.options,
+                None,
+            )?)),
+            // Commands::Update => Ok(Args::Update),
+            Commands::Cache(cli) => {
+                let config = load_config()?;
+                let dir = match cli.cache_dir {
+                    Some(dir) => dir,
+                    None => config
+                        .get_string("General.cache_dir")
                         .ok()
-                        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-                        .map(|p| p.join("config.toml"));
-                    let mut config = Config::builder();
-                    if let Some(config_path) = self_config_path {
-                        config = config.add_source(File::from(config_path).required(false));
-                    }
-                    let config = config
-                        .add_source(File::with_name("fast-down.toml").required(false))
-                        .add_source(Environment::with_prefix("FD"))
-                        .build()?;
-                    if let Ok(value) = config.get_bool("General.force") {
-                        args.force = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.resume") {
-                        args.resume = value;
-                    }
-                    if let Ok(value) = config.get_string("General.save_folder") {
-                        args.save_folder = value.into();
-                    }
-                    if let Ok(value) = config.get_int("General.threads") {
-                        args.threads = value.try_into()?;
-                    }
-                    if let Ok(value) = config.get_string("General.proxy")
-                        && !value.is_empty()
-                    {
-                        args.proxy = Some(value);
-                    }
-                    if let Ok(value) = config.get_int("General.write_buffer_size") {
-                        args.write_buffer_size = value.try_into()?;
-                    }
-                    if let Ok(value) = config.get_int("General.write_queue_cap") {
-                        args.write_queue_cap = value.try_into()?;
-                    }
-                    if let Ok(value) = config.get_int("General.progress_width") {
-                        args.progress_width = value.try_into()?;
-                    }
-                    if let Ok(value) = config.get_int("General.retry_gap") {
-                        args.retry_gap = Duration::from_millis(value.try_into()?);
-                    }
-                    if let Ok(value) = config.get_int("General.repaint_gap") {
-                        args.repaint_gap = Duration::from_millis(value.try_into()?);
-                    }
-                    if let Ok(value) = config.get_bool("General.browser") {
-                        args.browser = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.yes") {
-                        args.yes = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.no") {
-                        args.no = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.verbose") {
-                        args.verbose = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.multiplexing") {
-                        args.multiplexing = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.accept_invalid_hostnames") {
-                        args.accept_invalid_hostnames = value;
-                    }
-                    if let Ok(value) = config.get_bool("General.accept_invalid_certs") {
-                        args.accept_invalid_certs = value;
-                    }
-                    if let Ok(table) = config.get_table("Headers") {
-                        for (key, value) in table {
-                            let value_str = value.to_string();
-                            match HeaderName::from_str(&key) {
-                                Ok(header_name) => match value_str.parse() {
-                                    Ok(header_value) => {
-                                        args.headers.insert(header_name, header_value);
-                                    }
-                                    Err(e) => {
-                                        eprintln!(
-                                            "无法解析请求头值\n请求头: {key}: {value_str}\n错误原因: {e:?}",
-                                        );
-                                    }
-                                },
-                                Err(e) => {
-                                    eprintln!("无法解析请求头名称\n请求头: {key}\n错误原因: {e:?}",);
-                                }
-                            }
-                        }
-                    }
-                    args.force = cli.force;
-                    args.resume = cli.resume;
-                    if let Some(value) = cli.save_folder {
-                        args.save_folder = value.into();
-                    }
-                    if let Some(value) = cli.threads {
-                        args.threads = value;
-                    }
-                    if let Some(value) = cli.proxy {
-                        args.proxy.replace(value);
-                    }
-                    if let Some(value) = cli.write_buffer_size {
-                        args.write_buffer_size = value;
-                    }
-                    if let Some(value) = cli.write_queue_cap {
-                        args.write_queue_cap = value;
-                    }
-                    if let Some(value) = cli.progress_width {
-                        args.progress_width = value;
-                    }
-                    if let Some(value) = cli.retry_gap {
-                        args.retry_gap = Duration::from_millis(value);
-                    }
-                    if let Some(value) = cli.repaint_gap {
-                        args.repaint_gap = Duration::from_millis(value);
-                    }
-                    if cli.browser {
-                        args.browser = true;
-                    }
-                    args.yes = cli.yes;
-                    args.no = cli.no;
-                    args.verbose = cli.verbose;
-                    args.multiplexing = cli.multiplexing;
-                    args.accept_invalid_hostnames = cli.accept_invalid_hostnames;
-                    args.accept_invalid_certs = cli.accept_invalid_hostnames;
-                    for header in cli.headers {
-                        let parts: Vec<_> = header.splitn(2, ':').map(|t| t.trim()).collect();
-                        if parts.len() != 2 {
-                            eprintln!("请求头格式错误: {header}");
-                            continue;
-                        }
-                        args.headers
-                            .insert(HeaderName::from_str(parts[0])?, parts[1].parse()?);
-                    }
-                    Ok(Args::Download(args))
+                        .filter(|dir| !dir.is_empty())
+                        .ok_or_else(|| eyre!("{}", t!("err.cache-dir")))?
+                        .into(),
+                };
+                let action = match cli.command {
+                    CacheCommands::Ls { json } => CacheAction::Ls { json },
+                    CacheCommands::Prune {
+                        max_size,
+                        older_than,
+                        all,
+                    } => CacheAction::Prune {
+                        max_size: match max_size {
+                            Some(size) => Some(size),
+                            None => config
+                                .get_string("General.cache_max_size")
+                                .ok()
+                                .map(|s| fmt::parse_size(&s))
+                                .transpose()
+                                .map_err(|e| eyre!(e))?,
+                        },
+                        older_than,
+                        all,
+                    },
+                    CacheCommands::Size => CacheAction::Size,
+                };
+                Ok(Args::Cache(CacheArgs { dir, action }))
+            }
+            Commands::Clean(cli) => Ok(Args::Clean(CleanArgs {
+                older_than: cli.older_than,
+                pattern: cli.pattern.as_deref().map(Pattern::new).transpose()?,
+                missing: cli.missing,
+                delete_files: cli.delete_files,
+                dry_run: cli.dry_run,
+            })),
+            Commands::Config(cli) => match cli.command {
+                ConfigCommands::Show { profile, url } => {
+                    let (config, profile) = load_profile_config(
+                        url.as_deref().unwrap_or_default(),
+                        profile.as_deref(),
+                    )?;
+                    Ok(Args::Config(ConfigArgs {
+                        files: config_files()
+                            .into_iter()
+                            .map(|path| {
+                                let exists = path.is_file();
+                                (path, exists)
+                            })
+                            .collect(),
+                        config,
+                        profile,
+                    }))
                 }
-                // Commands::Update => Ok(Args::Update),
-                Commands::Clean => Ok(Args::Clean),
-                Commands::List => Ok(Args::List),
             },
-            Err(err) => err.exit(),
+            Commands::History(cli) => Ok(Args::History(HistoryArgs {
+                filter: cli.filter,
+                limit: cli.limit,
+                json: cli.json,
+            })),
+            Commands::List(cli) => Ok(Args::List(ListArgs {
+                filter: cli.filter,
+                redactor: config_redactor(&load_config()?),
+                json: cli.json,
+                incomplete: cli.incomplete,
+                complete: cli.complete,
+            })),
+            Commands::Mirror(cli) => Ok(Args::Mirror(MirrorArgs {
+                download: DownloadArgs::new(cli.download.url, cli.download.options, None)?,
+                include: cli
+                    .include
+                    .iter()
+                    .map(|p| Pattern::new(p))
+                    .collect::<Result<_, _>>()?,
+                exclude: cli
+                    .exclude
+                    .iter()
+                    .map(|p| Pattern::new(p))
+                    .collect::<Result<_, _>>()?,
+            })),
+            Commands::Resume(cli) if cli.url.is_some() && cli.paths.len() > 1 => {
+                Err(eyre!("{}", t!("err.resume-url-paths")))
+            }
+            Commands::Resume(cli) => Ok(Args::Resume(ResumeArgs::new(cli)?)),
+            Commands::Stats(cli) => Ok(Args::Stats(StatsArgs {
+                days: cli.days,
+                json: cli.json,
+            })),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_profile() {
+        let profile = |name: &str, patterns: &[&str]| {
+            (
+                name.to_string(),
+                patterns.iter().map(|p| Pattern::new(p).unwrap()).collect(),
+            )
+        };
+        let profiles = vec![
+            profile("any", &["*"]),
+            profile("corp", &["*.corp.example", "files.example.com"]),
+            profile("dev", &["*.dev.corp.example"]),
+            profile("other", &["*.corp.example"]),
+        ];
+        assert_eq!(match_profile(&profiles, "a.corp.example"), Some("corp"));
+        assert_eq!(match_profile(&profiles, "a.dev.corp.example"), Some("dev"));
+        assert_eq!(match_profile(&profiles, "files.example.com"), Some("corp"));
+        assert_eq!(match_profile(&profiles, "example.org"), Some("any"));
+        assert_eq!(match_profile(&profiles[1..], "example.org"), None);
+    }
+
+    #[test]
+    fn test_resume_profile_uses_original_host() {
+        let dir = env::temp_dir().join(format!("fd-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.toml");
+        std::fs::write(
+            &config_path,
+            "[profile.old]\nmatch = \"old.example\"\n[profile.new]\nmatch = \"new.example\"\n",
+        )
+        .unwrap();
+        let _ = CONFIG_PATH.set(config_path);
+        let Commands::Resume(cli) = Cli::try_parse_from([
+            "fast",
+            "resume",
+            "a.bin",
+            "--url",
+            "https://new.example/a.bin",
+        ])
+        .unwrap()
+        .command
+        else {
+            unreachable!()
+        };
+        let args = ResumeArgs::new(cli).unwrap();
+        let entry = DatabaseEntry {
+            file_path: b"/tmp/a.bin".to_vec(),
+            file_name: "a.bin".to_string(),
+            file_size: 0,
+            etag: None,
+            last_modified: None,
+            progress: vec![],
+            elapsed: 0,
+            url: "https://old.example/a.bin".to_string(),
+            options: None,
+            sha256: None,
+        };
+        let download_args = args.entry_args(&entry).unwrap();
+        assert_eq!(download_args.profile.as_deref(), Some("old"));
+        assert_eq!(download_args.url, entry.url);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use std::{env, fmt, fs, path::PathBuf};
+use url::Url;
+
+/// 下载凭据, 只会发送给用户指定链接所在的主机
+#[derive(Clone, Default)]
+pub struct Credentials {
+    /// 凭据的作用范围, 即用户输入的链接
+    pub scope: Option<Url>,
+    pub user: Option<String>,
+    pub bearer: Option<String>,
+    /// 是否查找 .netrc
+    pub netrc: bool,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |s: &Option<String>| s.as_ref().map(|_| "<redacted>");
+        f.debug_struct("Credentials")
+            .field("scope", &self.scope.as_ref().map(Url::as_str))
+            .field("user", &redact(&self.user))
+            .field("bearer", &redact(&self.bearer))
+            .field("netrc", &self.netrc)
+            .finish()
+    }
+}
+
+fn same_host(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+fn basic(user: &str, password: &str) -> Option<HeaderValue> {
+    let token = BASE64_STANDARD.encode(format!("{user}:{password}"));
+    let mut value = HeaderValue::from_str(&format!("Basic {token}")).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
+
+impl Credentials {
+    /// 计算访问 `url` 时应携带的 Authorization 请求头
+    pub fn authorization(&self, url: &Url) -> Option<HeaderValue> {
+        if self
+            .scope
+            .as_ref()
+            .is_some_and(|scope| same_host(scope, url))
+        {
+            if let Some(token) = &self.bearer {
+                let mut value = HeaderValue::from_str(&format!("Bearer {token}")).ok()?;
+                value.set_sensitive(true);
+                return Some(value);
+            }
+            if let Some(user) = &self.user {
+                let (user, password) = user.split_once(':').unwrap_or((user, ""));
+                return basic(user, password);
+            }
+        }
+        if self.netrc {
+            let host = url.host_str()?;
+            let text = fs::read_to_string(netrc_path()?).ok()?;
+            let (login, password) = lookup_netrc(&text, host)?;
+            return basic(&login, &password);
+        }
+        None
+    }
+
+    /// 为访问 `url` 的客户端准备请求头
+    ///
+    /// 手动指定的 Authorization 同样只发送给用户输入的主机, 跨域重定向后会被移除
+    pub fn apply(&self, headers: &HeaderMap, url: &Url) -> HeaderMap {
+        let mut headers = headers.clone();
+        if !self
+            .scope
+            .as_ref()
+            .is_none_or(|scope| same_host(scope, url))
+        {
+            headers.remove(header::AUTHORIZATION);
+        }
+        if !headers.contains_key(header::AUTHORIZATION)
+            && let Some(value) = self.authorization(url)
+        {
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        headers
+    }
+}
+
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC") {
+        return Some(path.into());
+    }
+    let home = env::home_dir()?;
+    let path = home.join(".netrc");
+    if cfg!(windows) && !path.exists() {
+        return Some(home.join("_netrc"));
+    }
+    Some(path)
+}
+
+/// 在 .netrc 中查找主机对应的用户名和密码, 找不到时使用 default 条目
+fn lookup_netrc(text: &str, host: &str) -> Option<(String, String)> {
+    // 去掉以空行结束的 macdef 宏定义
+    let mut body = String::new();
+    let mut in_macro = false;
+    for line in text.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+        } else if line.split_whitespace().next() == Some("macdef") {
+            in_macro = true;
+        } else {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    let mut entries: Vec<(Option<&str>, Option<&str>, Option<&str>)> = Vec::new();
+    let mut tokens = body.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match (token, entries.last_mut()) {
+            ("machine", _) => entries.push((Some(tokens.next().unwrap_or_default()), None, None)),
+            ("default", _) => entries.push((None, None, None)),
+            ("login", Some(entry)) => entry.1 = tokens.next(),
+            ("password", Some(entry)) => entry.2 = tokens.next(),
+            ("account", _) => {
+                tokens.next();
+            }
+            _ => {}
+        }
+    }
+    entries
+        .iter()
+        .find(|e| e.0.is_some_and(|m| m.eq_ignore_ascii_case(host)))
+        .or_else(|| entries.iter().find(|e| e.0.is_none()))
+        .map(|(_, login, password)| {
+            (
+                login.unwrap_or_default().to_string(),
+                password.unwrap_or_default().to_string(),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_netrc() {
+        let text = "machine a.com login alice password p1\n\
+            macdef init\n\
+            machine evil.com login x password y\n\
+            \n\
+            machine b.com\n  login bob\n  account acc\n  password p2\n\
+            default login anon password guest\n";
+        let get = |host| lookup_netrc(text, host);
+        assert_eq!(get("a.com"), Some(("alice".into(), "p1".into())));
+        assert_eq!(get("B.com"), Some(("bob".into(), "p2".into())));
+        assert_eq!(get("evil.com"), Some(("anon".into(), "guest".into())));
+        assert_eq!(
+            lookup_netrc("machine a.com login a password b", "c.com"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cross_origin() {
+        let creds = Credentials {
+            scope: Url::parse("https://a.com/file").ok(),
+            bearer: Some("t".into()),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        let same = creds.apply(&headers, &Url::parse("https://a.com:443/x").unwrap());
+        assert_eq!(same[header::AUTHORIZATION], "Bearer t");
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic manual"),
+        );
+        let other = creds.apply(&headers, &Url::parse("https://cdn.b.com/x").unwrap());
+        assert!(!other.contains_key(header::AUTHORIZATION));
+    }
+}
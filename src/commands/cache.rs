@@ -0,0 +1,86 @@
+use crate::{
+    args::{CacheAction, CacheArgs},
+    fmt,
+    persist::Cache,
+};
+use color_eyre::{Result, eyre::eyre};
+
+pub async fn cache(args: CacheArgs) -> Result<()> {
+    let cache = Cache::new(args.dir);
+    let mut entries = cache.entries()?;
+    match args.action {
+        CacheAction::Ls { json } => {
+            entries.sort_by_key(|e| std::cmp::Reverse(e.last_used));
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+            if entries.is_empty() {
+                eprintln!("{}", t!("cache.empty"));
+                return Ok(());
+            }
+            let mut rows = vec![vec![
+                t!("cache.last-used").to_string(),
+                t!("list.name").to_string(),
+                t!("list.size").to_string(),
+                t!("cache.url").to_string(),
+            ]];
+            for entry in &entries {
+                rows.push(vec![
+                    fmt::format_datetime(entry.last_used),
+                    entry.file_name.clone(),
+                    fmt::format_size(entry.size as f64),
+                    entry.url.clone(),
+                ]);
+            }
+            print!("{}", fmt::render_table(&rows, &[false, false, true, false]));
+        }
+        CacheAction::Prune {
+            max_size,
+            older_than,
+            all,
+        } => {
+            if !all && older_than.is_none() && max_size.is_none() {
+                return Err(eyre!("{}", t!("err.cache-max-size")));
+            }
+            let mut removed = 0;
+            let mut freed = 0;
+            if all || older_than.is_some() {
+                let deadline = older_than.map_or(u64::MAX, |age| {
+                    fmt::unix_now().saturating_sub(age.as_secs())
+                });
+                for entry in entries.iter().filter(|e| all || e.last_used < deadline) {
+                    cache.remove(&entry.key)?;
+                    removed += 1;
+                    freed += entry.size;
+                }
+            }
+            if !all && let Some(max_size) = max_size {
+                for entry in cache.prune_to(max_size)? {
+                    removed += 1;
+                    freed += entry.size;
+                }
+            }
+            eprintln!(
+                "{}",
+                t!(
+                    "msg.cache-pruned",
+                    count = removed,
+                    size = fmt::format_size(freed as f64)
+                )
+            );
+        }
+        CacheAction::Size => {
+            let total: u64 = entries.iter().map(|e| e.size).sum();
+            println!(
+                "{}",
+                t!(
+                    "msg.cache-size",
+                    count = entries.len(),
+                    size = fmt::format_size(total as f64)
+                )
+            );
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,74 @@
-use crate::persist::Database;
+use crate::{args::CleanArgs, persist::Database};
 use color_eyre::Result;
+use std::{fs, time::SystemTime};
 
-pub async fn clean() -> Result<()> {
+pub async fn clean(args: CleanArgs) -> Result<()> {
     let db = Database::new().await?;
-    let len = db.clean_finished().await?;
+    // 未指定任何条件时, 只清除已下载完成的链接
+    let finished_only = args.older_than.is_none() && args.pattern.is_none() && !args.missing;
+    let now = SystemTime::now();
+    let selected: Vec<_> = db
+        .entries()
+        .await
+        .into_iter()
+        .filter(|entry| {
+            let path = entry.path();
+            let exists = path.exists();
+            if finished_only {
+                return entry.is_finished();
+            }
+            if args.missing && exists {
+                return false;
+            }
+            if let Some(pattern) = &args.pattern
+                && !pattern.matches_path(&path)
+                && !pattern.matches(&entry.url)
+            {
+                return false;
+            }
+            if let Some(age) = args.older_than {
+                let modified = fs::metadata(&path).and_then(|m| m.modified());
+                // 文件不存在时无法判断修改时间, 视为已过期
+                if let Ok(modified) = modified
+                    && now.duration_since(modified).unwrap_or_default() < age
+                {
+                    return false;
+                }
+            }
+            true
+        })
+        .collect();
+
+    let mut deleted = 0;
+    let mut file_paths = Vec::with_capacity(selected.len());
+    for entry in &selected {
+        let path = entry.path();
+        let delete = args.delete_files && !entry.is_finished() && path.exists();
+        if delete {
+            eprintln!("  {} ({})", path.display(), t!("msg.clean-delete-file"));
+        } else {
+            eprintln!("  {}", path.display());
+        }
+        if delete && !args.dry_run {
+            match fs::remove_file(&path) {
+                Ok(()) => deleted += 1,
+                // 保留记录, 以便之后再次清理
+                Err(err) => {
+                    eprintln!("{}: {:?}", t!("err.clean-delete-file"), err);
+                    continue;
+                }
+            }
+        }
+        file_paths.push(entry.file_path.clone());
+    }
+    if args.dry_run {
+        eprintln!("{}", t!("msg.clean-dry-run", count = selected.len()));
+        return Ok(());
+    }
+    let len = db.remove_entries(&file_paths).await?;
     eprintln!("{}", t!("msg.clean", count = len));
+    if deleted > 0 {
+        eprintln!("{}", t!("msg.clean-deleted-files", count = deleted));
+    }
     Ok(())
 }
@@ -0,0 +1,167 @@
+use crate::{
+    args::ConfigArgs,
+    redact::{self, REDACTED, Redactor},
+};
+use color_eyre::Result;
+use config::{Config, Map, Value, ValueKind};
+use reqwest::header::HeaderName;
+use std::str::FromStr;
+use unicode_width::UnicodeWidthStr;
+
+/// 把配置值格式化为 TOML
+fn format_value(value: &Value) -> String {
+    match &value.kind {
+        ValueKind::Nil => "\"\"".to_string(),
+        ValueKind::Boolean(v) => v.to_string(),
+        ValueKind::I64(v) => v.to_string(),
+        ValueKind::I128(v) => v.to_string(),
+        ValueKind::U64(v) => v.to_string(),
+        ValueKind::U128(v) => v.to_string(),
+        ValueKind::Float(v) => v.to_string(),
+        ValueKind::String(v) => format!("{v:?}"),
+        ValueKind::Array(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(format_value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        ValueKind::Table(table) => {
+            let mut keys: Vec<_> = table.keys().collect();
+            keys.sort();
+            format!(
+                "{{ {} }}",
+                keys.iter()
+                    .map(|k| format!("{} = {}", format_key(k), format_value(&table[*k])))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+}
+
+fn format_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_string()
+    } else {
+        format!("{key:?}")
+    }
+}
+
+/// 隐藏敏感请求头和代理密码
+fn redact_value(section: &str, key: &str, value: &Value) -> String {
+    let redactor = Redactor::default();
+    match (section, &value.kind) {
+        ("Headers", _)
+            if HeaderName::from_str(key).is_ok_and(|name| redact::is_sensitive_header(&name)) =>
+        {
+            format!("{REDACTED:?}")
+        }
+        ("General", ValueKind::String(v)) if key == "proxy" => {
+            format!("{:?}", redactor.url_str(v))
+        }
+        _ => format_value(value),
+    }
+}
+
+/// 输出一个表中的值, 嵌套的表 (如 `[profile.<name>.Headers]`) 单独输出
+fn print_table(config: &Config, path: &str) -> Result<()> {
+    let table = config.get_table(path)?;
+    let section = path.rsplit('.').next().unwrap_or(path);
+    let mut children = Vec::new();
+    let mut lines = Vec::new();
+    for (key, value) in &table {
+        if matches!(value.kind, ValueKind::Table(_)) {
+            children.push(key);
+            continue;
+        }
+        let origin = match value.origin() {
+            Some("the environment") | None => t!("config.env").to_string(),
+            Some(origin) => origin.to_string(),
+        };
+        lines.push((
+            format!(
+                "{} = {}",
+                format_key(key),
+                redact_value(section, key, value)
+            ),
+            origin,
+        ));
+    }
+    if !lines.is_empty() {
+        lines.sort();
+        let width = lines.iter().map(|(l, _)| l.width()).max().unwrap_or(0);
+        println!(
+            "\n[{}]",
+            path.split('.')
+                .map(format_key)
+                .collect::<Vec<_>>()
+                .join(".")
+        );
+        for (line, origin) in lines {
+            println!("{line}{} # {origin}", " ".repeat(width - line.width()));
+        }
+    }
+    children.sort();
+    for key in children {
+        print_table(config, &format!("{path}.{key}"))?;
+    }
+    Ok(())
+}
+
+pub fn show(args: ConfigArgs) -> Result<()> {
+    println!("# {}", t!("config.files"));
+    for (path, exists) in &args.files {
+        if *exists {
+            println!("#   {}", path.display());
+        } else {
+            println!("#   {} ({})", path.display(), t!("config.not-found"));
+        }
+    }
+    if let Some(profile) = &args.profile {
+        println!("#   {}", t!("config.profile", name = profile));
+    }
+    println!("#   {}", t!("config.env"));
+
+    // 顶层只有来自环境变量的零散值 (如 FD_BEARER), 只显示各个表
+    let root: Map<String, Value> = args.config.clone().try_deserialize()?;
+    let mut sections: Vec<_> = root
+        .into_iter()
+        .filter(|(_, v)| matches!(v.kind, ValueKind::Table(_)))
+        .map(|(k, _)| k)
+        .collect();
+    sections.sort();
+    for section in sections {
+        print_table(&args.config, &section)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_value() {
+        let value = |kind: ValueKind| Value::new(None, kind);
+        assert_eq!(format_value(&value(ValueKind::I64(8))), "8");
+        assert_eq!(
+            format_value(&value(ValueKind::String("a \"b\"".into()))),
+            r#""a \"b\"""#
+        );
+        assert_eq!(
+            format_value(&value(ValueKind::Array(vec![
+                value(ValueKind::String("sig".into())),
+                value(ValueKind::Boolean(true)),
+            ]))),
+            r#"["sig", true]"#
+        );
+        assert_eq!(format_key("sec-ch-ua"), "sec-ch-ua");
+        assert_eq!(format_key("a b"), r#""a b""#);
+    }
+}
@@ -1,18 +1,23 @@
 use crate::space::check_free_space;
 use crate::{
     args::DownloadArgs,
+    cookies::CookieJar,
+    dedupe::{self, Dedupe},
     fmt,
-    persist::Database,
+    persist::{Cache, Database, DatabaseEntry, HistoryRecord, Outcome},
     progress::{self, Painter as ProgressPainter},
-    puller::{FastDownPuller, build_client},
+    puller::{FastDownPuller, WindowedPuller, build_client},
+    pusher::{
+        self, FileWriter, RandFilePusherDirect, RandFilePusherPwrite, ReorderWindow,
+        StdoutRandPusher, StdoutSeqPusher,
+    },
+    timestamping,
 };
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, eyre};
 #[cfg(target_pointer_width = "64")]
 use fast_pull::file::RandFilePusherMmap;
-#[cfg(not(target_pointer_width = "64"))]
-use fast_pull::file::RandFilePusherStd;
 use fast_pull::{
-    Event, MergeProgress, ProgressEntry, Total,
+    Event, MergeProgress, ProgressEntry, Total, UrlInfo,
     file::SeqFilePusher,
     multi::{self, download_multi},
     reqwest::Prefetch,
@@ -23,17 +28,39 @@ use std::num::NonZero;
 use std::{
     env,
     num::NonZeroUsize,
+    path::{Path, PathBuf},
     sync::Arc,
     time::{Duration, Instant},
 };
 use tokio::fs;
 use tokio::{
     fs::OpenOptions,
-    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{self, AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, SeekFrom},
     sync::Mutex,
 };
 use url::Url;
 
+/// 复用已有文件完成下载时的历史记录, 没有下载任何数据
+fn reused_record(info: &UrlInfo, save_path: &Path, redacted_url: &str) -> HistoryRecord {
+    let now = fmt::unix_now();
+    HistoryRecord {
+        file_name: info.name.clone(),
+        path: save_path.to_string_lossy().into_owned(),
+        url: redacted_url.to_string(),
+        host: info.final_url.host_str().unwrap_or_default().to_string(),
+        size: info.size,
+        started_at: now,
+        finished_at: now,
+        elapsed: 0,
+        downloaded: 0,
+        redownloaded: 0,
+        retries: 0,
+        speed: 0.0,
+        resumed: false,
+        outcome: Outcome::Completed,
+    }
+}
+
 macro_rules! predicate {
     ($args:expr) => {
         if ($args.yes) {
@@ -88,7 +115,29 @@ fn cancel_expected() -> Result<()> {
     Ok(())
 }
 
-pub async fn download(mut args: DownloadArgs) -> Result<()> {
+/// 续传校验不通过时取消, 严格续传模式下视为失败
+fn cancel_mismatch(strict: bool) -> Result<()> {
+    if strict {
+        return Err(eyre!("{}", t!("err.resume-mismatch")));
+    }
+    cancel_expected()
+}
+
+pub async fn download(args: DownloadArgs) -> Result<()> {
+    let cookie_jar = Arc::new(CookieJar::load(args.load_cookies.as_deref())?);
+    let save_cookies = args.save_cookies.clone();
+    let result = download_with_cookies(args, cookie_jar.clone()).await;
+    if let Some(path) = save_cookies {
+        cookie_jar.save(&path)?;
+    }
+    result
+}
+
+/// 使用共享的 Cookie 存储下载, 供批量下载在多个任务间复用会话
+pub async fn download_with_cookies(
+    mut args: DownloadArgs,
+    cookie_jar: Arc<CookieJar>,
+) -> Result<()> {
     if args.browser {
         let url = Url::parse(&args.url)?;
         args.headers
@@ -101,20 +150,26 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
             .or_insert(HeaderValue::from_str(&args.url)?);
     }
     if args.verbose {
-        dbg!(&args);
+        dbg!(args.redacted());
+        if let Some(profile) = &args.profile {
+            eprintln!("{}", t!("verbose.profile", name = profile));
+        }
     }
     let client = build_client(
-        &args.headers,
+        &args
+            .credentials
+            .apply(&args.headers, &Url::parse(&args.url)?),
         &args.proxy,
         args.accept_invalid_certs,
         args.accept_invalid_hostnames,
+        &cookie_jar,
     )?;
-    let db = Database::new().await?;
+    let db = Database::new().await?.with_redactor(args.redactor.clone());
 
     let info = loop {
         match client.prefetch(&args.url).await {
             Ok(info) => break info,
-            Err(err) => eprintln!("{}: {:#?}", t!("err.url-info"), err),
+            Err(err) => eprintln!("{}: {:#?}", t!("err.url-info"), args.redactor.error(err)),
         }
         tokio::time::sleep(args.retry_gap).await;
     };
@@ -123,40 +178,89 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
     } else {
         None
     };
-    let mut save_path = args
-        .save_folder
-        .join(args.file_name.as_ref().unwrap_or(&info.name));
-    if save_path.is_relative()
-        && let Ok(current_dir) = env::current_dir()
-    {
-        save_path = current_dir.join(save_path);
-    }
-    save_path = path_clean::clean(save_path);
+    let to_stdout = args.file_name.as_deref() == Some("-");
+    let save_path = if to_stdout {
+        PathBuf::from("-")
+    } else {
+        let mut save_path = args
+            .save_folder
+            .join(args.file_name.as_ref().unwrap_or(&info.name));
+        if save_path.is_relative()
+            && let Ok(current_dir) = env::current_dir()
+        {
+            save_path = current_dir.join(save_path);
+        }
+        path_clean::clean(save_path)
+    };
+    let part_path = {
+        let mut part_path = save_path.clone().into_os_string();
+        part_path.push(&args.part_suffix);
+        PathBuf::from(part_path)
+    };
 
     eprintln!(
         "{}",
         fmt::format_download_info(&info, &save_path, concurrent)
     );
 
+    if !to_stdout
+        && args.timestamping
+        && save_path.try_exists()?
+        && timestamping::is_unchanged(&db, &save_path, &info).await?
+    {
+        eprintln!("{}", t!("msg.not-modified"));
+        return Ok(());
+    }
+
     #[allow(clippy::single_range_in_vec_init)]
     let mut download_chunks = vec![0..info.size];
     let mut resume_download = false;
     let mut write_progress: Vec<ProgressEntry> =
         Vec::with_capacity(concurrent.map(NonZeroUsize::get).unwrap_or(1));
     let mut elapsed = 0;
+    let mut seq_start = 0;
+    // 严格续传模式下自动拒绝与记录矛盾的文件; 无法校验 (弱 ETag、没有 ETag) 时仍然询问
+    let mismatch_predicate = if args.strict_resume {
+        Some(false)
+    } else {
+        predicate!(args)
+    };
 
-    if save_path.try_exists()? {
-        if args.resume
-            && info.fast_download
-            && let Some(entry) = db.get_entry(&save_path).await
-        {
-            let downloaded = entry.progress.total();
-            if downloaded < info.size {
-                download_chunks = progress::invert(&entry.progress, info.size);
-                write_progress = entry.progress.clone();
-                resume_download = true;
-                elapsed = entry.elapsed;
-                eprintln!("{}", t!("msg.resume-download"));
+    if !to_stdout
+        && args.resume
+        && info.supports_range
+        && part_path.try_exists()?
+        && let Some(mut entry) = match db.get_entry(&part_path).await {
+            Some(entry) => Some(entry),
+            None => db.import_sidecar(&part_path).await?,
+        }
+    {
+        if !info.fast_download {
+            // 单线程下载只能从连续的开头部分继续
+            seq_start = entry
+                .progress
+                .first()
+                .filter(|p| p.start == 0)
+                .map_or(0, |p| p.end);
+            entry.progress.truncate(1);
+            entry.progress.retain(|p| p.start == 0);
+        }
+        let downloaded = entry.progress.total();
+        if downloaded > 0 && (info.size == 0 || downloaded < info.size) {
+            download_chunks = progress::invert(&entry.progress, info.size);
+            write_progress = entry.progress.clone();
+            resume_download = true;
+            elapsed = entry.elapsed;
+            eprintln!("{}", t!("msg.resume-download"));
+            if info.size == 0 {
+                eprintln!(
+                    "{}",
+                    t!(
+                        "msg.download-unknown",
+                        completed = fmt::format_size(downloaded as f64)
+                    ),
+                );
+            } else {
                 eprintln!(
                     "{}",
                     t!(
@@ -166,129 +270,331 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
                         percentage = downloaded * 100 / info.size
                     ),
                 );
-                if entry.file_size != info.size
-                    && !confirm(
-                        predicate!(args),
-                        &t!(
-                            "msg.size-mismatch",
-                            saved_size = entry.file_size,
-                            new_size = info.size
-                        ),
-                        false,
-                    )
-                    .await?
-                {
-                    return cancel_expected();
-                }
-                if entry.etag != info.etag {
-                    if !confirm(
-                        predicate!(args),
-                        &t!(
-                            "msg.etag-mismatch",
-                            saved_etag = entry.etag : {:?},
-                            new_etag = info.etag : {:?}
-                        ),
-                        false,
-                    )
-                    .await?
-                    {
-                        return cancel_expected();
-                    }
-                } else if let Some(ref progress_etag) = entry.etag
-                    && progress_etag.starts_with("W/")
-                {
-                    if !confirm(
-                        predicate!(args),
-                        &t!("msg.weak-etag", etag = progress_etag),
-                        false,
-                    )
-                    .await?
-                    {
-                        return cancel_expected();
-                    }
-                } else if entry.etag.is_none()
-                    && !confirm(predicate!(args), &t!("msg.no-etag"), false).await?
+            }
+            // 之前大小未知的下载不需要确认大小
+            if entry.file_size != 0
+                && entry.file_size != info.size
+                && !confirm(
+                    mismatch_predicate,
+                    &t!(
+                        "msg.size-mismatch",
+                        saved_size = entry.file_size,
+                        new_size = info.size
+                    ),
+                    false,
+                )
+                .await?
+            {
+                return cancel_mismatch(args.strict_resume);
+            }
+            if entry.etag != info.etag {
+                // 只有两边都有 ETag 时才能确定不一致
+                let contradicts = entry.etag.is_some() && info.etag.is_some();
+                if !confirm(
+                    if contradicts {
+                        mismatch_predicate
+                    } else {
+                        predicate!(args)
+                    },
+                    &t!(
+                        "msg.etag-mismatch",
+                        saved_etag = entry.etag : {:?},
+                        new_etag = info.etag : {:?}
+                    ),
+                    false,
+                )
+                .await?
                 {
-                    return cancel_expected();
+                    return cancel_mismatch(args.strict_resume && contradicts);
                 }
-                if entry.last_modified != info.last_modified
-                    && !confirm(
-                        predicate!(args),
-                        &t!(
-                            "msg.last-modified-mismatch",
-                            saved_last_modified = entry.last_modified : {:?},
-                            new_last_modified = info.last_modified : {:?}
-                        ),
-                        false,
-                    )
-                    .await?
+            } else if let Some(ref progress_etag) = entry.etag
+                && progress_etag.starts_with("W/")
+            {
+                if !confirm(
+                    predicate!(args),
+                    &t!("msg.weak-etag", etag = progress_etag),
+                    false,
+                )
+                .await?
                 {
                     return cancel_expected();
                 }
+            } else if entry.etag.is_none()
+                && !confirm(predicate!(args), &t!("msg.no-etag"), false).await?
+            {
+                return cancel_expected();
+            }
+            let contradicts = entry.last_modified.is_some() && info.last_modified.is_some();
+            if entry.last_modified != info.last_modified
+                && !confirm(
+                    if contradicts {
+                        mismatch_predicate
+                    } else {
+                        predicate!(args)
+                    },
+                    &t!(
+                        "msg.last-modified-mismatch",
+                        saved_last_modified = entry.last_modified : {:?},
+                        new_last_modified = info.last_modified : {:?}
+                    ),
+                    false,
+                )
+                .await?
+            {
+                return cancel_mismatch(args.strict_resume && contradicts);
             }
         }
-        if !args.yes
-            && !resume_download
+    }
+    // 上次下载已完成但没有重命名 (如重命名前被中断), 远端未改变时直接重命名
+    if !to_stdout
+        && !resume_download
+        && info.size > 0
+        && part_path.try_exists()?
+        && let Some(entry) = match db.get_entry(&part_path).await {
+            Some(entry) => Some(entry),
+            None => db.import_sidecar(&part_path).await?,
+        }
+        && entry.file_size == info.size
+        && entry.progress.total() == info.size
+        && (entry.etag.is_some() || entry.last_modified.is_some())
+        && entry.etag == info.etag
+        && entry.last_modified == info.last_modified
+        && fs::metadata(&part_path).await?.len() == info.size
+    {
+        if save_path.try_exists()?
+            && !args.yes
             && !args.force
             && !confirm(predicate!(args), &t!("msg.file-overwrite"), false).await?
         {
             return cancel_expected();
         }
+        eprintln!("{}", t!("msg.part-complete"));
+        fs::rename(&part_path, &save_path).await?;
+        if args.timestamping
+            && let Some(ref last_modified) = info.last_modified
+        {
+            timestamping::set_mtime(&save_path, last_modified)?;
+        }
+        db.rename_entry(&part_path, &save_path).await?;
+        return Ok(());
+    }
+    if args.strict_resume && !resume_download {
+        return Err(eyre!("{}", t!("err.resume-unavailable")));
+    }
+    if !to_stdout
+        && !resume_download
+        && save_path.try_exists()?
+        && !args.yes
+        && !args.force
+        && !args.timestamping
+        && !confirm(predicate!(args), &t!("msg.file-overwrite"), false).await?
+    {
+        return cancel_expected();
     }
-    if let Some(size) = check_free_space(&save_path, download_chunks.total())? {
+    if !to_stdout && !resume_download && part_path.try_exists()? {
+        fs::remove_file(&part_path).await?;
+    }
+    let redacted_url = args.redactor.url(&info.final_url).to_string();
+    if !to_stdout
+        && !resume_download
+        && args.dedupe != Dedupe::Off
+        && let Some(source) = dedupe::find_source(
+            db.entries().await,
+            &args.redactor,
+            &redacted_url,
+            info.size,
+            info.etag.as_deref(),
+            &save_path,
+            args.dedupe_verify,
+        )
+        .await?
+    {
+        let source_path = source.path();
+        eprintln!("{}", t!("msg.dedupe", path = source_path.display()));
+        if let Some(parent) = save_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        dedupe::reuse(args.dedupe, source_path, part_path.clone()).await?;
+        fs::rename(&part_path, &save_path).await?;
+        if args.timestamping
+            && let Some(ref last_modified) = info.last_modified
+        {
+            timestamping::set_mtime(&save_path, last_modified)?;
+        }
+        let record = reused_record(&info, &save_path, &redacted_url);
+        // 进度和 SHA-256 沿用来源文件, 其余字段以本次响应为准
+        db.put_entry(DatabaseEntry {
+            file_path: save_path.as_os_str().as_encoded_bytes().to_vec(),
+            file_name: info.name,
+            etag: info.etag,
+            last_modified: info.last_modified,
+            elapsed: 0,
+            url: info.final_url.to_string(),
+            options: Some(args.saved_options()?),
+            ..source
+        })
+        .await?;
+        if let Err(e) = db.add_history(record).await {
+            eprintln!("{}\n{:?}", t!("err.history-write"), e);
+        }
+        return Ok(());
+    }
+    if !to_stdout
+        && !resume_download
+        && let Some(cache_dir) = &args.cache_dir
+    {
+        if let Some(parent) = save_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let cache = Cache::new(cache_dir.clone());
+        match cache
+            .restore(
+                &redacted_url,
+                info.size,
+                info.etag.as_deref(),
+                info.last_modified.as_deref(),
+                &part_path,
+            )
+            .await
+        {
+            Ok(Some(cached)) => {
+                eprintln!("{}", t!("msg.cache-hit"));
+                fs::rename(&part_path, &save_path).await?;
+                if args.timestamping
+                    && let Some(ref last_modified) = info.last_modified
+                {
+                    timestamping::set_mtime(&save_path, last_modified)?;
+                }
+                let record = reused_record(&info, &save_path, &redacted_url);
+                #[allow(clippy::single_range_in_vec_init)]
+                db.put_entry(DatabaseEntry {
+                    file_path: save_path.as_os_str().as_encoded_bytes().to_vec(),
+                    file_name: info.name,
+                    file_size: info.size,
+                    etag: info.etag,
+                    last_modified: info.last_modified,
+                    progress: vec![0..info.size],
+                    elapsed: 0,
+                    url: info.final_url.to_string(),
+                    options: Some(args.saved_options()?),
+                    sha256: cached.sha256(),
+                })
+                .await?;
+                if let Err(e) = db.add_history(record).await {
+                    eprintln!("{}\n{:?}", t!("err.history-write"), e);
+                }
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("{}\n{:?}", t!("err.cache-read"), err);
+                let _ = fs::remove_file(&part_path).await;
+            }
+        }
+    }
+    if !to_stdout && let Some(size) = check_free_space(&part_path, download_chunks.total())? {
         eprintln!(
             "{}",
             t!("msg.lack-of-space", size = fmt::format_size(size as f64)),
         );
         return cancel_expected();
     }
-    let puller = FastDownPuller::new(
+    let mut puller = FastDownPuller::new(
         info.final_url.clone(),
-        args.headers,
-        args.proxy,
+        args.credentials.apply(&args.headers, &info.final_url),
+        args.proxy.clone(),
         args.multiplexing,
         args.accept_invalid_certs,
         args.accept_invalid_hostnames,
+        cookie_jar,
     )?;
-    if let Some(parent) = save_path.parent()
+    if !to_stdout
+        && let Some(parent) = save_path.parent()
         && let Err(err) = fs::create_dir_all(parent).await
         && err.kind() != std::io::ErrorKind::AlreadyExists
     {
         return Err(err.into());
     }
-    let result = if info.fast_download {
-        #[cfg(target_pointer_width = "64")]
-        let pusher = RandFilePusherMmap::new(&save_path, info.size, args.write_buffer_size).await?;
-        #[cfg(not(target_pointer_width = "64"))]
-        let pusher = {
-            let file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .read(true)
-                .truncate(false)
-                .open(&save_path)
-                .await?;
-            RandFilePusherStd::new(file, info.size, args.write_buffer_size).await?
+    // mmap 映射前总会把文件设为完整长度, 无法不分配空间
+    #[cfg(target_pointer_width = "64")]
+    if !to_stdout
+        && info.fast_download
+        && args.writer == FileWriter::Mmap
+        && args.file_allocation == pusher::FileAllocation::None
+    {
+        return Err(eyre!("{}", t!("err.allocation-mmap")));
+    }
+    if !to_stdout
+        && info.size > 0
+        && let Err(err) = pusher::allocate(&part_path, info.size, args.file_allocation).await
+    {
+        if err.kind() != std::io::ErrorKind::StorageFull {
+            return Err(err.into());
+        }
+        eprintln!("{}: {}", t!("err.file-allocation"), err);
+        return cancel_expected();
+    }
+    let result = if to_stdout {
+        if info.fast_download {
+            let window = Arc::new(ReorderWindow::new(args.write_buffer_size as u64));
+            download_multi(
+                WindowedPuller::new(puller, window.clone()),
+                StdoutRandPusher::new(window),
+                multi::DownloadOptions {
+                    download_chunks,
+                    retry_gap: args.retry_gap,
+                    concurrent: concurrent.unwrap(),
+                    push_queue_cap: args.write_queue_cap,
+                    min_chunk_size: NonZero::new(8 * 1024).unwrap(),
+                },
+            )
+            .await
+        } else {
+            download_single(
+                puller,
+                StdoutSeqPusher::new(args.write_buffer_size),
+                single::DownloadOptions {
+                    retry_gap: args.retry_gap,
+                    push_queue_cap: args.write_queue_cap,
+                },
+            )
+            .await
+        }
+    } else if info.fast_download {
+        let options = multi::DownloadOptions {
+            download_chunks,
+            retry_gap: args.retry_gap,
+            concurrent: concurrent.unwrap(),
+            push_queue_cap: args.write_queue_cap,
+            min_chunk_size: NonZero::new(8 * 1024).unwrap(),
         };
-        download_multi(
-            puller,
-            pusher,
-            multi::DownloadOptions {
-                download_chunks,
-                retry_gap: args.retry_gap,
-                concurrent: concurrent.unwrap(),
-                push_queue_cap: args.write_queue_cap,
-                min_chunk_size: NonZero::new(8 * 1024).unwrap(),
-            },
-        )
-        .await
+        match args.writer {
+            #[cfg(target_pointer_width = "64")]
+            FileWriter::Mmap => {
+                let pusher =
+                    RandFilePusherMmap::new(&part_path, info.size, args.write_buffer_size).await?;
+                download_multi(puller, pusher, options).await
+            }
+            FileWriter::Pwrite => {
+                let pusher = RandFilePusherPwrite::new(&part_path, args.write_buffer_size).await?;
+                download_multi(puller, pusher, options).await
+            }
+            FileWriter::Direct => {
+                let pusher =
+                    RandFilePusherDirect::new(&part_path, info.size, args.write_buffer_size)
+                        .await?;
+                download_multi(puller, pusher, options).await
+            }
+        }
     } else {
-        let file = OpenOptions::new()
+        let mut file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(false)
-            .open(&save_path)
+            .open(&part_path)
             .await?;
+        file.seek(SeekFrom::Start(seq_start)).await?;
+        puller.set_start(seq_start);
         let pusher = SeqFilePusher::new(file, args.write_buffer_size);
         download_single(
             puller,
@@ -309,18 +615,27 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
 
     let mut last_db_update = Instant::now();
 
-    if !resume_download {
+    if !to_stdout && !resume_download {
         db.init_entry(
-            &save_path,
-            info.name,
+            &part_path,
+            info.name.clone(),
             info.size,
-            info.etag,
-            info.last_modified,
+            info.etag.clone(),
+            info.last_modified.clone(),
             info.final_url.to_string(),
+            args.saved_options()?,
         )
         .await?;
+    } else if resume_download {
+        db.set_url(&part_path, info.final_url.to_string()).await?;
     }
 
+    let started_at = fmt::unix_now();
+    let session_start = Instant::now();
+    let initial_downloaded = write_progress.total();
+    let mut pulled = 0;
+    let mut retries = 0;
+
     let start = Instant::now() - Duration::from_millis(elapsed);
     let painter = Arc::new(Mutex::new(ProgressPainter::new(
         write_progress.clone(),
@@ -331,16 +646,24 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
         start,
     )));
     let painter_handle = ProgressPainter::start_update_thread(painter.clone());
+    let mut finished = false;
     while let Ok(e) = result.event_chain.recv().await {
         match e {
-            Event::PullProgress(_, p) => painter.lock().await.add(p),
+            Event::PullProgress(_, p) => {
+                pulled += p.end - p.start;
+                painter
+                    .lock()
+                    .await
+                    .add(p.start + seq_start..p.end + seq_start)
+            }
             Event::PushProgress(_, p) => {
+                let p = p.start + seq_start..p.end + seq_start;
                 write_progress.merge_progress(p);
-                if last_db_update.elapsed().as_millis() >= 500 {
+                if !to_stdout && last_db_update.elapsed().as_millis() >= 500 {
                     last_db_update = Instant::now();
                     let res = db
                         .update_entry(
-                            &save_path,
+                            &part_path,
                             write_progress.clone(),
                             start.elapsed().as_millis() as u64,
                         )
@@ -354,12 +677,15 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
                     }
                 }
             }
-            Event::PullError(id, err) => painter.lock().await.print(&format!(
-                "{} {}\n{:?}\n",
-                t!("verbose.worker-id", id = id),
-                t!("verbose.download-error"),
-                err
-            ))?,
+            Event::PullError(id, err) => {
+                retries += 1;
+                painter.lock().await.print(&format!(
+                    "{} {}\n{:?}\n",
+                    t!("verbose.worker-id", id = id),
+                    t!("verbose.download-error"),
+                    args.redactor.error(err)
+                ))?
+            }
             Event::PushError(_, err) => painter.lock().await.print(&format!(
                 "{}\n{:?}\n",
                 t!("verbose.write-error"),
@@ -380,6 +706,7 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
                 }
             }
             Event::Finished(id) => {
+                finished = true;
                 if args.verbose {
                     painter.lock().await.print(&format!(
                         "{} {}\n",
@@ -390,18 +717,108 @@ pub async fn download(mut args: DownloadArgs) -> Result<()> {
             }
         }
     }
-    db.update_entry(
-        &save_path,
-        write_progress.clone(),
-        start.elapsed().as_millis() as u64,
-    )
-    .await?;
-    if let Err(e) = result.join().await
+    if !to_stdout {
+        db.checkpoint_entry(
+            &part_path,
+            write_progress.clone(),
+            start.elapsed().as_millis() as u64,
+        )
+        .await?;
+    }
+    let join_result = result.join().await;
+    // Finished 事件每个线程触发一次, 大小已知时只按写入进度判断是否完成
+    let completed = if info.size > 0 {
+        write_progress.total() >= info.size
+    } else {
+        finished && join_result.is_ok()
+    };
+    let elapsed = session_start.elapsed().as_millis() as u64;
+    let outcome = match &join_result {
+        Err(e) if !e.is_cancelled() => Outcome::Failed,
+        _ if completed => Outcome::Completed,
+        _ => Outcome::Cancelled,
+    };
+    let record = HistoryRecord {
+        file_name: info.name.clone(),
+        path: save_path.to_string_lossy().into_owned(),
+        url: redacted_url.clone(),
+        host: info.final_url.host_str().unwrap_or_default().to_string(),
+        size: info.size,
+        started_at,
+        finished_at: fmt::unix_now(),
+        elapsed,
+        downloaded: pulled,
+        // 接收的字节数超出新增进度的部分
+        redownloaded: pulled
+            .saturating_sub(write_progress.total().saturating_sub(initial_downloaded)),
+        retries,
+        speed: if elapsed > 0 {
+            pulled as f64 * 1000.0 / elapsed as f64
+        } else {
+            0.0
+        },
+        resumed: resume_download,
+        outcome,
+    };
+    if let Err(e) = db.add_history(record).await {
+        eprintln!("{}\n{:?}", t!("err.history-write"), e);
+    }
+    if let Err(e) = join_result
         && !e.is_cancelled()
     {
         Err(e)?
     }
-    painter.lock().await.update()?;
+    if !to_stdout && completed {
+        db.set_file_size(&part_path, write_progress.total()).await?;
+        fs::rename(&part_path, &save_path).await?;
+        if args.timestamping
+            && let Some(ref last_modified) = info.last_modified
+        {
+            timestamping::set_mtime(&save_path, last_modified)?;
+        }
+        db.rename_entry(&part_path, &save_path).await?;
+        let sha256 = if args.dedupe_verify {
+            let path = save_path.clone();
+            let sha256 = tokio::task::spawn_blocking(move || dedupe::sha256_file(&path)).await??;
+            db.set_sha256(&save_path, sha256).await?;
+            Some(sha256)
+        } else {
+            None
+        };
+        if let Some(cache_dir) = &args.cache_dir
+            && info.size > 0
+        {
+            let cache = Cache::new(cache_dir.clone());
+            let result = match cache
+                .store(
+                    &save_path,
+                    redacted_url,
+                    info.name,
+                    info.size,
+                    info.etag,
+                    info.last_modified,
+                    sha256,
+                )
+                .await
+            {
+                Ok(()) => match args.cache_max_size {
+                    Some(max_size) => cache.prune_to(max_size).map(|_| ()),
+                    None => Ok(()),
+                },
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                eprintln!("{}\n{:?}", t!("err.cache-write"), err);
+            }
+        }
+    }
+    {
+        let mut painter = painter.lock().await;
+        if completed && painter.file_size == 0 {
+            painter.file_size = painter.curr_size;
+        }
+        painter.update()?;
+    }
     painter_handle.abort();
     if let Err(e) = painter_handle.await
         && !e.is_cancelled()
@@ -0,0 +1,76 @@
+use crate::{
+    args::HistoryArgs,
+    fmt,
+    persist::{Database, Outcome},
+};
+use color_eyre::Result;
+
+fn outcome_text(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Completed => t!("history.completed"),
+        Outcome::Failed => t!("history.failed"),
+        Outcome::Cancelled => t!("history.cancelled"),
+    }
+    .to_string()
+}
+
+pub async fn history(args: HistoryArgs) -> Result<()> {
+    let db = Database::new().await?;
+    let mut records = db.history().await?;
+    records.retain(|record| {
+        args.filter
+            .as_ref()
+            .is_none_or(|f| record.path.contains(f) || record.url.contains(f))
+    });
+    // 只保留最近的记录, 按时间先后显示
+    records.drain(..records.len().saturating_sub(args.limit));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+    if records.is_empty() {
+        eprintln!("{}", t!("history.empty"));
+        return Ok(());
+    }
+    let mut rows = vec![vec![
+        t!("history.finished-at").to_string(),
+        t!("list.name").to_string(),
+        t!("list.size").to_string(),
+        t!("list.elapsed").to_string(),
+        t!("list.speed").to_string(),
+        t!("history.retries").to_string(),
+        t!("history.redownloaded").to_string(),
+        t!("history.host").to_string(),
+        t!("history.outcome").to_string(),
+    ]];
+    for record in &records {
+        rows.push(vec![
+            fmt::format_datetime(record.finished_at),
+            record.file_name.clone(),
+            if record.size > 0 {
+                fmt::format_size(record.size as f64)
+            } else {
+                t!("list.unknown").to_string()
+            },
+            fmt::format_time(record.elapsed / 1000),
+            if record.speed > 0.0 {
+                format!("{}/s", fmt::format_size(record.speed))
+            } else {
+                "-".to_string()
+            },
+            record.retries.to_string(),
+            fmt::format_size(record.redownloaded as f64),
+            record.host.clone(),
+            outcome_text(record.outcome),
+        ]);
+    }
+    print!(
+        "{}",
+        fmt::render_table(
+            &rows,
+            &[false, false, true, true, true, true, true, false, false]
+        )
+    );
+    Ok(())
+}
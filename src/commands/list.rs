@@ -1,8 +1,130 @@
-use crate::persist::Database;
+use crate::{args::ListArgs, fmt, persist::Database};
 use color_eyre::Result;
+use fast_pull::Total;
+use serde::Serialize;
 
-pub async fn list() -> Result<()> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum State {
+    Complete,
+    Partial,
+    Missing,
+}
+
+impl State {
+    fn text(self) -> String {
+        match self {
+            State::Complete => t!("list.complete"),
+            State::Partial => t!("list.partial"),
+            State::Missing => t!("list.missing"),
+        }
+        .to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ListItem {
+    name: String,
+    path: String,
+    url: String,
+    size: u64,
+    downloaded: u64,
+    /// 单位: ms
+    elapsed: u64,
+    /// 平均速度, 单位: B/s
+    speed: f64,
+    state: State,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+pub async fn list(args: ListArgs) -> Result<()> {
     let db = Database::new().await?;
-    eprintln!("{db:#?}");
+    let items: Vec<_> = db
+        .entries()
+        .await
+        .into_iter()
+        .map(|entry| {
+            let path = entry.path();
+            let downloaded = entry.progress.total();
+            let state = if !path.exists() {
+                State::Missing
+            } else if entry.is_finished() {
+                State::Complete
+            } else {
+                State::Partial
+            };
+            ListItem {
+                name: entry.file_name,
+                path: path.to_string_lossy().into_owned(),
+                url: args.redactor.url_str(&entry.url),
+                size: entry.file_size,
+                downloaded,
+                elapsed: entry.elapsed,
+                speed: if entry.elapsed > 0 {
+                    downloaded as f64 * 1000.0 / entry.elapsed as f64
+                } else {
+                    0.0
+                },
+                state,
+                etag: entry.etag,
+                last_modified: entry.last_modified,
+            }
+        })
+        .filter(|item| match item.state {
+            State::Complete => !args.incomplete,
+            State::Partial | State::Missing => !args.complete,
+        })
+        .filter(|item| {
+            args.filter
+                .as_ref()
+                .is_none_or(|f| item.path.contains(f) || item.url.contains(f))
+        })
+        .collect();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if items.is_empty() {
+        eprintln!("{}", t!("list.empty"));
+        return Ok(());
+    }
+    let mut rows = vec![vec![
+        t!("list.name").to_string(),
+        t!("list.size").to_string(),
+        t!("list.progress").to_string(),
+        t!("list.elapsed").to_string(),
+        t!("list.speed").to_string(),
+        t!("list.state").to_string(),
+        t!("list.path").to_string(),
+    ]];
+    for item in &items {
+        let (size, progress) = if item.size > 0 {
+            (
+                fmt::format_size(item.size as f64),
+                format!("{:.1}%", item.downloaded as f64 * 100.0 / item.size as f64),
+            )
+        } else {
+            (t!("list.unknown").to_string(), "-".to_string())
+        };
+        rows.push(vec![
+            item.name.clone(),
+            size,
+            progress,
+            fmt::format_time(item.elapsed / 1000),
+            if item.speed > 0.0 {
+                format!("{}/s", fmt::format_size(item.speed))
+            } else {
+                "-".to_string()
+            },
+            item.state.text(),
+            item.path.clone(),
+        ]);
+    }
+    print!(
+        "{}",
+        fmt::render_table(&rows, &[false, true, true, true, true, false, false])
+    );
     Ok(())
 }
@@ -0,0 +1,210 @@
+use crate::{
+    args::MirrorArgs, commands::download, cookies::CookieJar, links, puller::build_client, tasks,
+};
+use color_eyre::Result;
+use glob::{MatchOptions, Pattern};
+use reqwest::{Client, header};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+use url::Url;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+fn is_wanted(path: &str, include: &[Pattern], exclude: &[Pattern]) -> bool {
+    (include.is_empty() || include.iter().any(|p| p.matches_with(path, MATCH_OPTIONS)))
+        && !exclude.iter().any(|p| p.matches_with(path, MATCH_OPTIONS))
+}
+
+/// 相对于根目录的路径, 不在根目录下时返回 None
+fn relative_path(root: &Url, url: &Url) -> Option<String> {
+    if url.origin() != root.origin() {
+        return None;
+    }
+    let path = url.path().strip_prefix(root.path())?;
+    path.split('/')
+        .map(decode_segment)
+        .collect::<Option<Vec<_>>>()
+        .map(|segments| segments.join("/"))
+}
+
+/// 解码路径中的一段, 拒绝会跳出保存目录的 `.` 和 `..`
+fn decode_segment(s: &str) -> Option<String> {
+    let decoded = urlencoding::decode(s).ok()?;
+    (decoded != "." && decoded != ".." && !decoded.contains(['/', '\\']))
+        .then(|| decoded.into_owned())
+}
+
+/// 镜像的根目录: HTML 索引页本身就是目录 (即使链接末尾没有 `/`), 链接列表取其所在目录
+fn listing_root(base: &Url, is_html: bool) -> Url {
+    let mut root = base.clone();
+    root.set_query(None);
+    root.set_fragment(None);
+    if !is_html {
+        return root.join("./").unwrap_or(root);
+    }
+    if !root.path().ends_with('/') {
+        let path = format!("{}/", root.path());
+        root.set_path(&path);
+    }
+    root
+}
+
+/// 获取目录索引页, 返回重定向后的地址, 是否为 HTML 和内容
+async fn fetch_listing(client: &Client, url: &Url) -> reqwest::Result<(Url, bool, String)> {
+    let resp = client.get(url.clone()).send().await?.error_for_status()?;
+    let is_html = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("html"));
+    let base = resp.url().clone();
+    Ok((base, is_html, resp.text().await?))
+}
+
+pub async fn mirror(args: MirrorArgs) -> Result<()> {
+    let cookie_jar = Arc::new(CookieJar::load(args.download.load_cookies.as_deref())?);
+    let start = Url::parse(&args.download.url)?;
+    let client = build_client(
+        &args
+            .download
+            .credentials
+            .apply(&args.download.headers, &start),
+        &args.download.proxy,
+        args.download.accept_invalid_certs,
+        args.download.accept_invalid_hostnames,
+        &cookie_jar,
+    )?;
+    // 根目录由第一个索引页决定
+    let mut root = None;
+
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(mut page) = queue.pop_front() {
+        page.set_fragment(None);
+        if !visited.insert(page.clone()) {
+            continue;
+        }
+        let (base, is_html, body) = match fetch_listing(&client, &page).await {
+            Ok(listing) => listing,
+            Err(err) => {
+                eprintln!(
+                    "{}: {}\n{:?}",
+                    t!("err.mirror-list"),
+                    args.download.redactor.url(&page),
+                    args.download.redactor.error(err)
+                );
+                continue;
+            }
+        };
+        let root = root.get_or_insert_with(|| listing_root(&base, is_html));
+        let links = if is_html {
+            links::extract_html_links(&body)
+        } else {
+            links::extract_text_links(&body)
+        };
+        for link in links {
+            let Ok(mut url) = base.join(&link) else {
+                continue;
+            };
+            url.set_fragment(None);
+            if url.query().is_some() && url.path() == base.path() {
+                // 索引页的排序链接
+                continue;
+            }
+            let rel = relative_path(root, &url);
+            if url.path().ends_with('/') {
+                if is_html && rel.as_ref().is_some_and(|rel| !rel.is_empty()) {
+                    queue.push_back(url);
+                }
+                continue;
+            }
+            let rel = match rel {
+                Some(rel) => rel,
+                // 链接列表中的文件可以不在根目录下
+                None if !is_html => match url
+                    .path_segments()
+                    .and_then(|mut s| s.next_back())
+                    .and_then(decode_segment)
+                {
+                    Some(name) => name,
+                    None => continue,
+                },
+                None => continue,
+            };
+            if !rel.is_empty() && is_wanted(&rel, &args.include, &args.exclude) {
+                files.push((url, rel));
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    tasks::run(files, |(url, rel)| {
+        let mut download_args = args.download.clone();
+        download_args.url = url.to_string();
+        let (dir, name) = rel.rsplit_once('/').unwrap_or(("", &rel));
+        download_args.save_folder = download_args.save_folder.join(dir);
+        download_args.file_name = Some(name.to_string());
+        download::download_with_cookies(download_args, cookie_jar.clone())
+    })
+    .await;
+    if let Some(path) = &args.download.save_cookies {
+        cookie_jar.save(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        let root = Url::parse("https://example.com/pub/").unwrap();
+        let rel = |s| relative_path(&root, &Url::parse(s).unwrap());
+        assert_eq!(
+            rel("https://example.com/pub/a/b.iso"),
+            Some("a/b.iso".into())
+        );
+        assert_eq!(
+            rel("https://example.com/pub/a%20b/c+d.txt"),
+            Some("a b/c+d.txt".into())
+        );
+        assert_eq!(rel("https://example.com/other/b.iso"), None);
+        assert_eq!(rel("https://mirror.example.com/pub/b.iso"), None);
+    }
+
+    #[test]
+    fn test_listing_root() {
+        let root = |s, is_html| listing_root(&Url::parse(s).unwrap(), is_html).to_string();
+        assert_eq!(
+            root("https://example.com/docs", true),
+            "https://example.com/docs/"
+        );
+        assert_eq!(
+            root("https://example.com/docs/?C=M", true),
+            "https://example.com/docs/"
+        );
+        assert_eq!(
+            root("https://example.com/pub/list.txt", false),
+            "https://example.com/pub/"
+        );
+    }
+
+    #[test]
+    fn test_is_wanted() {
+        let include = [Pattern::new("*.iso").unwrap()];
+        let exclude = [Pattern::new("old/**").unwrap()];
+        assert!(is_wanted("a/b.iso", &include, &exclude));
+        assert!(!is_wanted("a/b.txt", &include, &exclude));
+        assert!(!is_wanted("old/b.iso", &include, &exclude));
+        assert!(is_wanted("a/b.txt", &[], &[]));
+    }
+}
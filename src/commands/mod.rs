@@ -1,4 +1,10 @@
+pub(crate) mod cache;
 pub(crate) mod clean;
+pub(crate) mod config;
 pub(crate) mod download;
+pub(crate) mod history;
 pub(crate) mod list;
+pub(crate) mod mirror;
+pub(crate) mod resume;
+pub(crate) mod stats;
 pub(crate) mod update;
@@ -0,0 +1,127 @@
+use crate::{
+    args::ResumeArgs,
+    commands::download,
+    persist::{Database, DatabaseEntry},
+    redact::REDACTED,
+    tasks,
+};
+use color_eyre::{Result, eyre::eyre};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// 按最终文件路径或临时文件路径查找下载记录
+async fn find_entry(
+    db: &Database,
+    path: &Path,
+    part_suffix: &str,
+) -> Result<Option<DatabaseEntry>> {
+    let mut path = path.to_path_buf();
+    if path.is_relative()
+        && let Ok(current_dir) = env::current_dir()
+    {
+        path = current_dir.join(path);
+    }
+    let path = path_clean::clean(path);
+    if let Some(entry) = db.get_entry(&path).await {
+        return Ok(Some(entry));
+    }
+    let mut part_path = OsString::from(&path);
+    part_path.push(part_suffix);
+    if let Some(entry) = db.get_entry(&part_path).await {
+        return Ok(Some(entry));
+    }
+    // 下载时使用了其他临时文件后缀
+    if let Some(entry) = db.entries().await.into_iter().find(|e| {
+        e.options.as_ref().is_some_and(|options| {
+            let mut part_path = OsString::from(&path);
+            part_path.push(&options.part_suffix);
+            e.file_path == part_path.as_encoded_bytes()
+        })
+    }) {
+        return Ok(Some(entry));
+    }
+    // 全局数据库中没有记录 (如从其他机器复制而来) 时读取状态文件
+    if let Some(entry) = db.import_sidecar(&part_path).await? {
+        return Ok(Some(entry));
+    }
+    db.import_sidecar(&path).await
+}
+
+async fn resume_entry(entry: DatabaseEntry, url: Option<String>, args: &ResumeArgs) -> Result<()> {
+    let mut download_args = args.entry_args(&entry)?;
+    // 只有换用新链接时才需要拒绝与记录矛盾的文件
+    download_args.strict_resume = url.is_some();
+    download_args.url = match url {
+        Some(url) => url,
+        // 从状态文件导入的链接已隐藏签名, 无法直接使用
+        None if entry.url.contains(REDACTED) => {
+            return Err(eyre!("{}", t!("err.resume-redacted-url")));
+        }
+        None => entry.url.clone(),
+    };
+    download_args.credentials.scope = Url::parse(&download_args.url).ok();
+
+    // 从临时文件路径还原出最终保存路径
+    let part_path = entry.path();
+    let suffix = download_args.part_suffix.as_bytes();
+    let save_path = match entry.file_path.strip_suffix(suffix) {
+        Some(path) if !suffix.is_empty() => {
+            PathBuf::from(unsafe { OsStr::from_encoded_bytes_unchecked(path) })
+        }
+        _ => {
+            download_args.part_suffix.clear();
+            part_path.clone()
+        }
+    };
+    // 已完成但临时文件还没有重命名时交给下载流程重命名
+    if entry.is_finished() && (part_path == save_path || !part_path.exists()) {
+        eprintln!("{}", t!("msg.resume-finished"));
+        return Ok(());
+    }
+    download_args.save_folder = save_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    download_args.file_name = save_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    download_args.resume = true;
+    download_args.timestamping = false;
+    download::download(download_args).await
+}
+
+pub async fn resume(args: ResumeArgs) -> Result<()> {
+    let db = Database::new().await?;
+    let entries = if args.all {
+        db.entries()
+            .await
+            .into_iter()
+            .filter(|e| !e.is_finished() && e.path().exists())
+            .collect()
+    } else {
+        let mut entries = Vec::with_capacity(args.paths.len());
+        for path in &args.paths {
+            let entry = find_entry(&db, path, &args.download.part_suffix)
+                .await?
+                .ok_or_else(|| eyre!("{}: {}", t!("err.resume-not-found"), path.display()))?;
+            entries.push(entry);
+        }
+        entries
+    };
+    drop(db);
+
+    if !args.all && entries.len() == 1 {
+        let entry = entries.into_iter().next().unwrap();
+        return resume_entry(entry, args.url.clone(), &args).await;
+    }
+    if entries.is_empty() {
+        eprintln!("{}", t!("err.empty-tasks"));
+        return Ok(());
+    }
+    tasks::run(entries, |entry| resume_entry(entry, None, &args)).await;
+    Ok(())
+}
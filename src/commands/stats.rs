@@ -0,0 +1,206 @@
+use crate::{
+    args::StatsArgs,
+    fmt,
+    persist::{Database, HistoryRecord, Outcome},
+};
+use color_eyre::Result;
+use fmt::ONE_DAY;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct HostStats {
+    host: String,
+    downloads: u64,
+    completed: u64,
+    failed: u64,
+    /// 接收的字节数
+    bytes: u64,
+    /// 单位: ms
+    elapsed: u64,
+    retries: u64,
+    redownloaded: u64,
+    /// 平均速度, 单位: B/s
+    speed: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DayStats {
+    /// UTC 日期
+    date: String,
+    downloads: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    hosts: Vec<HostStats>,
+    days: Vec<DayStats>,
+}
+
+/// 按主机汇总全部历史, 按日汇总 `since` 之后结束的下载
+fn summarize(records: &[HistoryRecord], since: u64) -> Stats {
+    let mut hosts: HashMap<&str, HostStats> = HashMap::new();
+    let mut days: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+    for record in records {
+        let host = hosts.entry(&record.host).or_insert_with(|| HostStats {
+            host: record.host.clone(),
+            ..Default::default()
+        });
+        host.downloads += 1;
+        match record.outcome {
+            Outcome::Completed => host.completed += 1,
+            Outcome::Failed => host.failed += 1,
+            Outcome::Cancelled => {}
+        }
+        host.bytes += record.downloaded;
+        host.elapsed += record.elapsed;
+        host.retries += record.retries;
+        host.redownloaded += record.redownloaded;
+        if record.finished_at >= since {
+            let day = days.entry(record.finished_at / ONE_DAY).or_default();
+            day.0 += 1;
+            day.1 += record.downloaded;
+        }
+    }
+    let mut hosts: Vec<_> = hosts
+        .into_values()
+        .map(|mut host| {
+            if host.elapsed > 0 {
+                host.speed = host.bytes as f64 * 1000.0 / host.elapsed as f64;
+            }
+            host
+        })
+        .collect();
+    hosts.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.host.cmp(&b.host)));
+    let days = days
+        .into_iter()
+        .map(|(day, (downloads, bytes))| DayStats {
+            date: fmt::format_date(day * ONE_DAY),
+            downloads,
+            bytes,
+        })
+        .collect();
+    Stats { hosts, days }
+}
+
+pub async fn stats(args: StatsArgs) -> Result<()> {
+    let db = Database::new().await?;
+    let records = db.history().await?;
+    let today = fmt::unix_now() / ONE_DAY;
+    let since = today.saturating_sub(args.days.saturating_sub(1)) * ONE_DAY;
+    let stats = summarize(&records, since);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+    if stats.hosts.is_empty() {
+        eprintln!("{}", t!("history.empty"));
+        return Ok(());
+    }
+    let mut rows = vec![vec![
+        t!("history.host").to_string(),
+        t!("stats.downloads").to_string(),
+        t!("history.completed").to_string(),
+        t!("history.failed").to_string(),
+        t!("stats.bytes").to_string(),
+        t!("list.speed").to_string(),
+        t!("history.retries").to_string(),
+        t!("history.redownloaded").to_string(),
+    ]];
+    for host in &stats.hosts {
+        rows.push(vec![
+            host.host.clone(),
+            host.downloads.to_string(),
+            host.completed.to_string(),
+            host.failed.to_string(),
+            fmt::format_size(host.bytes as f64),
+            if host.speed > 0.0 {
+                format!("{}/s", fmt::format_size(host.speed))
+            } else {
+                "-".to_string()
+            },
+            host.retries.to_string(),
+            fmt::format_size(host.redownloaded as f64),
+        ]);
+    }
+    print!(
+        "{}",
+        fmt::render_table(&rows, &[false, true, true, true, true, true, true, true])
+    );
+
+    println!();
+    let mut rows = vec![vec![
+        t!("stats.date").to_string(),
+        t!("stats.downloads").to_string(),
+        t!("stats.bytes").to_string(),
+    ]];
+    for day in &stats.days {
+        rows.push(vec![
+            day.date.clone(),
+            day.downloads.to_string(),
+            fmt::format_size(day.bytes as f64),
+        ]);
+    }
+    print!("{}", fmt::render_table(&rows, &[false, true, true]));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(host: &str, finished_at: u64, outcome: Outcome) -> HistoryRecord {
+        HistoryRecord {
+            file_name: "a.bin".into(),
+            path: "/tmp/a.bin".into(),
+            url: format!("https://{host}/a.bin"),
+            host: host.into(),
+            size: 1000,
+            started_at: finished_at - 1,
+            finished_at,
+            elapsed: 1000,
+            downloaded: 1000,
+            redownloaded: 10,
+            retries: 1,
+            speed: 1000.0,
+            resumed: false,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let day = 20000 * ONE_DAY;
+        let records = [
+            record("a.example", day - 10, Outcome::Completed),
+            record("b.example", day + 10, Outcome::Failed),
+            record("a.example", day + 20, Outcome::Completed),
+        ];
+        let stats = summarize(&records, day);
+        assert_eq!(
+            stats.hosts[0],
+            HostStats {
+                host: "a.example".into(),
+                downloads: 2,
+                completed: 2,
+                failed: 0,
+                bytes: 2000,
+                elapsed: 2000,
+                retries: 2,
+                redownloaded: 20,
+                speed: 1000.0,
+            }
+        );
+        assert_eq!(stats.hosts[1].failed, 1);
+        assert_eq!(
+            stats.days,
+            [DayStats {
+                date: fmt::format_date(day),
+                downloads: 2,
+                bytes: 2000,
+            }]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use cookie::time::OffsetDateTime;
+use cookie_store::{CookieDomain, CookieExpiration, CookieStore, RawCookie};
+use reqwest::header::HeaderValue;
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::Path,
+    sync::{PoisonError, RwLock},
+};
+use url::Url;
+
+/// 所有下载线程共享的 Cookie 存储, 支持导入导出 Netscape 格式 (cookies.txt)
+#[derive(Debug, Default)]
+pub struct CookieJar(RwLock<CookieStore>);
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// 解析 cookies.txt 中的一行, 返回 Cookie 和与之匹配的请求地址
+fn parse_netscape_line(line: &str) -> Option<(RawCookie<'static>, Url)> {
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(line) => (line, true),
+        None if line.starts_with('#') => return None,
+        None => (line, false),
+    };
+    let mut fields = line.trim_end_matches(['\r', '\n']).splitn(7, '\t');
+    let domain = fields.next()?;
+    let include_subdomains = fields.next()?.eq_ignore_ascii_case("TRUE");
+    let path = fields.next()?;
+    let secure = fields.next()?.eq_ignore_ascii_case("TRUE");
+    let expires: i64 = fields.next()?.parse().ok()?;
+    let name = fields.next()?;
+    let value = fields.next().unwrap_or("");
+    let host = domain.trim_start_matches('.');
+    let url = Url::parse(&format!(
+        "{}://{}{}",
+        if secure { "https" } else { "http" },
+        host,
+        path
+    ))
+    .ok()?;
+    let mut cookie = RawCookie::build((name.to_string(), value.to_string()))
+        .path(path.to_string())
+        .secure(secure)
+        .http_only(http_only);
+    if include_subdomains {
+        cookie = cookie.domain(host.to_string());
+    }
+    if expires > 0 {
+        cookie = cookie.expires(OffsetDateTime::from_unix_timestamp(expires).ok()?);
+    }
+    Some((cookie.build(), url))
+}
+
+impl CookieJar {
+    /// 从 cookies.txt 导入, 文件不存在时返回空的存储
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let jar = Self::default();
+        let Some(path) = path else {
+            return Ok(jar);
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(jar),
+            Err(e) => return Err(e),
+        };
+        let mut store = jar.0.write().unwrap_or_else(PoisonError::into_inner);
+        for (cookie, url) in text.lines().filter_map(parse_netscape_line) {
+            let _ = store.insert_raw(&cookie, &url);
+        }
+        drop(store);
+        Ok(jar)
+    }
+
+    /// 以 Netscape 格式导出所有未过期的 Cookie
+    pub fn to_netscape(&self) -> String {
+        let store = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        let mut text = String::from("# Netscape HTTP Cookie File\n");
+        for cookie in store.iter_unexpired() {
+            let (domain, include_subdomains) = match &cookie.domain {
+                CookieDomain::HostOnly(domain) => (domain.clone(), "FALSE"),
+                CookieDomain::Suffix(domain) => (format!(".{domain}"), "TRUE"),
+                CookieDomain::NotPresent | CookieDomain::Empty => continue,
+            };
+            let expires = match cookie.expires {
+                CookieExpiration::AtUtc(time) => time.unix_timestamp(),
+                CookieExpiration::SessionEnd => 0,
+            };
+            let _ = writeln!(
+                text,
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                if cookie.http_only().unwrap_or(false) {
+                    HTTP_ONLY_PREFIX
+                } else {
+                    ""
+                },
+                domain,
+                include_subdomains,
+                cookie.path.as_ref(),
+                if cookie.secure().unwrap_or(false) {
+                    "TRUE"
+                } else {
+                    "FALSE"
+                },
+                expires,
+                cookie.name(),
+                cookie.value(),
+            );
+        }
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_netscape())
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| RawCookie::parse(v.to_string()).ok());
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .store_response_cookies(cookies, url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let value = self
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if value.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::cookie::CookieStore as _;
+
+    #[test]
+    fn test_netscape_roundtrip() {
+        let text = "# Netscape HTTP Cookie File\n\
+            .example.com\tTRUE\t/\tFALSE\t4102444800\tsession\tabc\n\
+            #HttpOnly_login.example.com\tFALSE\t/auth\tTRUE\t0\ttoken\tx=y\n\
+            # comment\n";
+        let jar = CookieJar::default();
+        for (cookie, url) in text.lines().filter_map(parse_netscape_line) {
+            jar.0.write().unwrap().insert_raw(&cookie, &url).unwrap();
+        }
+        let cookies = |url: &str| jar.cookies(&Url::parse(url).unwrap());
+        assert_eq!(
+            cookies("http://dl.example.com/file"),
+            Some(HeaderValue::from_static("session=abc"))
+        );
+        assert_eq!(
+            cookies("http://login.example.com/auth/x"),
+            Some(HeaderValue::from_static("session=abc"))
+        );
+        assert_eq!(cookies("https://other.com/"), None);
+        let saved = jar.to_netscape();
+        assert!(saved.contains(".example.com\tTRUE\t/\tFALSE\t4102444800\tsession\tabc\n"));
+        assert!(saved.contains("#HttpOnly_login.example.com\tFALSE\t/auth\tTRUE\t0\ttoken\tx=y\n"));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{persist::DatabaseEntry, redact::Redactor};
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// 复用已下载文件的方式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dedupe {
+    /// 总是重新下载
+    #[default]
+    Off,
+    /// 写时复制, 文件系统不支持时复制文件
+    Reflink,
+    /// 硬链接, 跨文件系统时复制文件
+    Hardlink,
+    /// 复制文件
+    Copy,
+}
+
+/// 判断记录是否为同一链接的同一份完整文件, 只信任强 ETag
+///
+/// `url` 为隐藏签名后的链接, 同一文件重新签名后的链接也视为同一链接
+fn is_same_file(
+    entry: &DatabaseEntry,
+    redactor: &Redactor,
+    url: &str,
+    size: u64,
+    etag: Option<&str>,
+) -> bool {
+    entry.is_finished()
+        && redactor.url_str(&entry.url) == url
+        && entry.file_size == size
+        && etag.is_some_and(|etag| !etag.starts_with("W/") && entry.etag.as_deref() == Some(etag))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// 在下载记录中查找可以复用的文件
+///
+/// `verify` 为真时只使用记录了 SHA-256 且校验通过的文件
+pub async fn find_source(
+    entries: Vec<DatabaseEntry>,
+    redactor: &Redactor,
+    url: &str,
+    size: u64,
+    etag: Option<&str>,
+    save_path: &Path,
+    verify: bool,
+) -> io::Result<Option<DatabaseEntry>> {
+    for entry in entries {
+        let path = entry.path();
+        if !is_same_file(&entry, redactor, url, size, etag)
+            || path == save_path
+            || !path
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.len() == size)
+        {
+            continue;
+        }
+        if !verify {
+            return Ok(Some(entry));
+        }
+        let Some(expected) = entry.sha256 else {
+            continue;
+        };
+        let actual = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || sha256_file(&path)
+        })
+        .await??;
+        if actual == expected {
+            return Ok(Some(entry));
+        }
+        eprintln!("{}", t!("msg.dedupe-hash-mismatch", path = path.display()));
+    }
+    Ok(None)
+}
+
+/// 按指定方式把 `from` 复用到 `to`, `to` 必须不存在
+pub async fn reuse(mode: Dedupe, from: PathBuf, to: PathBuf) -> io::Result<()> {
+    tokio::task::spawn_blocking(move || match mode {
+        Dedupe::Off => Ok(()),
+        Dedupe::Reflink => match reflink(&from, &to) {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                let _ = fs::remove_file(&to);
+                fs::copy(&from, &to).map(|_| ())
+            }
+        },
+        Dedupe::Hardlink => match fs::hard_link(&from, &to) {
+            Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+                fs::copy(&from, &to).map(|_| ())
+            }
+            result => result,
+        },
+        Dedupe::Copy => fs::copy(&from, &to).map(|_| ()),
+    })
+    .await?
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let src = File::open(from)?;
+    let dst = File::options().write(true).create_new(true).open(to)?;
+    if unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_from: &Path, _to: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_is_same_file() {
+        let entry = DatabaseEntry {
+            file_path: b"/tmp/a.bin".to_vec(),
+            file_name: "a.bin".to_string(),
+            file_size: 10,
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            progress: vec![0..10],
+            elapsed: 0,
+            url: "https://example.com/a.bin".to_string(),
+            options: None,
+            sha256: None,
+        };
+        let redactor = Redactor::default();
+        let url = "https://example.com/a.bin";
+        assert!(is_same_file(&entry, &redactor, url, 10, Some("\"abc\"")));
+        assert!(!is_same_file(&entry, &redactor, url, 11, Some("\"abc\"")));
+        assert!(!is_same_file(&entry, &redactor, url, 10, Some("\"abd\"")));
+        assert!(!is_same_file(&entry, &redactor, url, 10, None));
+        assert!(!is_same_file(
+            &entry,
+            &redactor,
+            "https://example.com/b.bin",
+            10,
+            Some("\"abc\"")
+        ));
+
+        let weak = DatabaseEntry {
+            etag: Some("W/\"abc\"".to_string()),
+            ..entry.clone()
+        };
+        assert!(!is_same_file(&weak, &redactor, url, 10, Some("W/\"abc\"")));
+
+        // 重新签名后的链接
+        let signed = DatabaseEntry {
+            url: "https://example.com/a.bin?token=old".to_string(),
+            ..entry.clone()
+        };
+        let resigned = redactor.url_str("https://example.com/a.bin?token=new");
+        assert!(is_same_file(
+            &signed,
+            &redactor,
+            &resigned,
+            10,
+            Some("\"abc\"")
+        ));
+        assert!(!is_same_file(&signed, &redactor, url, 10, Some("\"abc\"")));
+
+        let partial = DatabaseEntry {
+            progress: vec![0..5],
+            ..entry
+        };
+        assert!(!is_same_file(&partial, &redactor, url, 10, Some("\"abc\"")));
+    }
+}
@@ -1,7 +1,9 @@
 mod info;
 mod size;
+mod table;
 mod time;
 
 pub use info::*;
 pub use size::*;
+pub use table::*;
 pub use time::*;
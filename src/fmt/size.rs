@@ -10,10 +10,46 @@ pub fn format_size(mut size: f64) -> String {
     format!("{:.2} {}", size, UNITS[unit_index])
 }
 
+/// 解析带单位的大小, 如 `512M`, `10GB`, `1.5GiB`, 单位不区分大小写, 按 1024 进位
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value.parse().map_err(|_| format!("无效的大小: {text}"))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let exp = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => return Err(format!("无效的大小单位: {unit}")),
+    };
+    Ok((value * 1024f64.powi(exp)) as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512M"), Ok(512 * 1024 * 1024));
+        assert_eq!(parse_size("10gb"), Ok(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1.5KiB"), Ok(1536));
+        assert_eq!(parse_size("2 B"), Ok(2));
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("G").is_err());
+    }
+
     #[test]
     fn test_format_file_size() {
         assert_eq!(format_size(0.0), "0.00 B");
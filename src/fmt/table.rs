@@ -0,0 +1,48 @@
+use unicode_width::UnicodeWidthStr;
+
+/// 将多行文本排版为表格, `right` 指定需要右对齐的列
+pub fn render_table(rows: &[Vec<String>], right: &[bool]) -> String {
+    let mut widths = vec![0; rows.first().map_or(0, Vec::len)];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    let mut out = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            let pad = " ".repeat(widths[i] - cell.width());
+            if i > 0 {
+                line.push_str("  ");
+            }
+            if right.get(i).copied().unwrap_or(false) {
+                line.push_str(&pad);
+                line.push_str(cell);
+            } else {
+                line.push_str(cell);
+                line.push_str(&pad);
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let rows = vec![
+            vec!["名称".to_string(), "大小".to_string(), "x".to_string()],
+            vec!["a.bin".to_string(), "1 B".to_string(), "".to_string()],
+        ];
+        assert_eq!(
+            render_table(&rows, &[false, true, false]),
+            "名称   大小  x\na.bin   1 B\n"
+        );
+    }
+}
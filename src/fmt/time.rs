@@ -1,7 +1,16 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 const ONE_SECOND: u64 = 1;
 const ONE_MINUTE: u64 = ONE_SECOND * 60;
 const ONE_HOUR: u64 = ONE_MINUTE * 60;
-const ONE_DAY: u64 = ONE_HOUR * 24;
+pub const ONE_DAY: u64 = ONE_HOUR * 24;
+
+/// 当前的 Unix 时间戳, 单位: 秒
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
 
 pub fn format_time(time: u64) -> String {
     if time < ONE_DAY {
@@ -19,6 +28,54 @@ pub fn format_time(time: u64) -> String {
     }
 }
 
+/// 解析带单位的时长, 如 `45s`, `30m`, `12h`, `7d`, `2w`, 不带单位时为秒
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: u64 = value.parse().map_err(|_| format!("无效的时长: {text}"))?;
+    let unit = match unit {
+        "" | "s" => ONE_SECOND,
+        "m" => ONE_MINUTE,
+        "h" => ONE_HOUR,
+        "d" => ONE_DAY,
+        "w" => ONE_DAY * 7,
+        _ => return Err(format!("无效的时长单位: {unit}")),
+    };
+    value
+        .checked_mul(unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("时长过长: {text}"))
+}
+
+/// 将 Unix 时间戳格式化为 UTC 日期, 如 `2025-08-01`
+pub fn format_date(timestamp: u64) -> String {
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = timestamp / ONE_DAY + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// 将 Unix 时间戳格式化为 UTC 日期和时间, 如 `2025-08-01 12:30`
+pub fn format_datetime(timestamp: u64) -> String {
+    let remainder = timestamp % ONE_DAY;
+    format!(
+        "{} {:02}:{:02}",
+        format_date(timestamp),
+        remainder / ONE_HOUR,
+        remainder % ONE_HOUR / ONE_MINUTE
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +94,24 @@ mod tests {
         assert_eq!(format_time(95400), "1d 02:30:00");
         assert_eq!(format_time(8726399), "100d 23:59:59");
     }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951782400), "2000-02-29");
+        assert_eq!(format_date(1754051400), "2025-08-01");
+        assert_eq!(format_datetime(1754051400), "2025-08-01 12:30");
+        assert_eq!(format_datetime(1767225599), "2025-12-31 23:59");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(604800)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(1209600)));
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
 }
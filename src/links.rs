@@ -0,0 +1,89 @@
+/// 从目录索引页 (Apache/nginx autoindex) 中提取所有 href
+pub fn extract_html_links(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let lower = html.to_ascii_lowercase();
+    let mut rest = 0;
+    while let Some(pos) = lower[rest..].find("href") {
+        let mut i = rest + pos + 4;
+        rest = i;
+        let bytes = html.as_bytes();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'=') {
+            continue;
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let value = match bytes.get(i) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let start = i + 1;
+                match html[start..].find(quote as char) {
+                    Some(len) => &html[start..start + len],
+                    None => break,
+                }
+            }
+            Some(_) => {
+                let end = html[i..]
+                    .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                    .map_or(html.len(), |len| i + len);
+                &html[i..end]
+            }
+            None => break,
+        };
+        rest = i + value.len();
+        let value = value.trim();
+        if !value.is_empty() {
+            links.push(decode_entities(value));
+        }
+    }
+    links
+}
+
+/// 从纯文本链接列表中提取链接, 忽略空行和 `#` 开头的注释
+pub fn extract_text_links(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_html_links() {
+        let html = r#"<html><body><h1>Index of /pub/</h1><pre>
+<a href="?C=N;O=D">Name</a>
+<a href="../">Parent Directory</a>
+<A HREF = 'sub dir/'>sub dir/</A>
+<a href=file.iso>file.iso</a>
+<a href="a&amp;b.txt">a&amp;b.txt</a>
+</pre></body></html>"#;
+        assert_eq!(
+            extract_html_links(html),
+            ["?C=N;O=D", "../", "sub dir/", "file.iso", "a&b.txt"]
+        );
+    }
+
+    #[test]
+    fn test_extract_text_links() {
+        let text = "# mirror list\nhttps://example.com/a.bin\n\n  b/c.bin  \n";
+        assert_eq!(
+            extract_text_links(text),
+            ["https://example.com/a.bin", "b/c.bin"]
+        );
+    }
+}
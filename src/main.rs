@@ -1,10 +1,18 @@
 mod args;
+mod auth;
 mod commands;
+mod cookies;
+mod dedupe;
 mod fmt;
+mod links;
 mod persist;
 mod progress;
 mod puller;
+mod pusher;
+mod redact;
 mod space;
+mod tasks;
+mod timestamping;
 
 use args::Args;
 use color_eyre::Result;
@@ -38,7 +46,13 @@ async fn main() -> Result<()> {
     match args {
         Args::Download(args) => download::download(args).await,
         // Args::Update => update::update().await,
-        Args::Clean => clean::clean().await,
-        Args::List => list::list().await,
+        Args::Cache(args) => cache::cache(args).await,
+        Args::Clean(args) => clean::clean(args).await,
+        Args::Config(args) => config::show(args),
+        Args::History(args) => history::history(args).await,
+        Args::List(args) => list::list(args).await,
+        Args::Mirror(args) => mirror::mirror(args).await,
+        Args::Resume(args) => resume::resume(args).await,
+        Args::Stats(args) => stats::stats(args).await,
     }
 }
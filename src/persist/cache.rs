@@ -0,0 +1,312 @@
+//! 本地下载缓存
+//!
+//! 每个完整的下载按 "链接 + ETag (没有时用 Last-Modified)" 的 SHA-256 作为键保存为
+//! `<键>` 和描述文件 `<键>.json`。再次下载时先用预请求得到的 ETag 和大小判断缓存是否新鲜,
+//! 命中后直接从缓存复制。总大小超过上限时按最近使用时间淘汰。
+
+use crate::{
+    dedupe::{self, Dedupe},
+    fmt,
+};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const META_EXTENSION: &str = "json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    #[serde(skip)]
+    pub key: String,
+    /// 已隐藏签名的最终链接
+    pub url: String,
+    pub file_name: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub size: u64,
+    /// 十六进制的 SHA-256, 未知时为空
+    pub sha256: Option<String>,
+    /// 写入时间, Unix 时间戳
+    pub created_at: u64,
+    /// 最近使用时间, Unix 时间戳
+    pub last_used: u64,
+}
+
+impl CacheEntry {
+    /// 解析保存的 SHA-256, 未知或格式错误时返回 `None`
+    pub fn sha256(&self) -> Option<[u8; 32]> {
+        let hex = self.sha256.as_deref()?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0; 32];
+        for (byte, chunk) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        }
+        Some(bytes)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 缓存键, 没有强 ETag 和 Last-Modified 时无法判断新鲜度, 返回 `None`
+fn cache_key(url: &str, etag: Option<&str>, last_modified: Option<&str>) -> Option<String> {
+    let validator = match (etag, last_modified) {
+        (Some(etag), _) if !etag.starts_with("W/") => format!("etag:{etag}"),
+        (_, Some(last_modified)) => format!("last-modified:{last_modified}"),
+        _ => return None,
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(validator.as_bytes());
+    Some(to_hex(&hasher.finalize()))
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn object_path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    fn meta_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{META_EXTENSION}"))
+    }
+
+    fn write_meta(&self, entry: &CacheEntry) -> Result<()> {
+        write_atomic(
+            &self.meta_path(&entry.key),
+            serde_json::to_string_pretty(entry)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// 读取所有缓存项, 跳过描述文件损坏或缺少数据的项
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+        let mut entries = vec![];
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            if path.extension().is_none_or(|ext| ext != META_EXTENSION) {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            let Ok(mut entry) = serde_json::from_slice::<CacheEntry>(&bytes) else {
+                continue;
+            };
+            entry.key = key.to_string();
+            if self.object_path(key).is_file() {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 查找与远端一致的缓存项, 并把它复制到 `to`
+    ///
+    /// 记录了 SHA-256 的缓存项会先校验, 校验失败的缓存项会被删除
+    pub async fn restore(
+        &self,
+        url: &str,
+        size: u64,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        to: &Path,
+    ) -> Result<Option<CacheEntry>> {
+        let Some(key) = cache_key(url, etag, last_modified) else {
+            return Ok(None);
+        };
+        let this = self.clone();
+        let to = to.to_path_buf();
+        let entry = tokio::task::spawn_blocking(move || -> Result<_> {
+            let Ok(bytes) = fs::read(this.meta_path(&key)) else {
+                return Ok(None);
+            };
+            let mut entry: CacheEntry = serde_json::from_slice(&bytes)?;
+            entry.key = key;
+            let object = this.object_path(&entry.key);
+            if entry.size != size || !object.metadata().is_ok_and(|m| m.len() == size) {
+                return Ok(None);
+            }
+            if let Some(expected) = &entry.sha256
+                && to_hex(&dedupe::sha256_file(&object)?) != *expected
+            {
+                eprintln!("{}", t!("msg.cache-corrupt", path = object.display()));
+                this.remove(&entry.key)?;
+                return Ok(None);
+            }
+            entry.last_used = fmt::unix_now();
+            this.write_meta(&entry)?;
+            Ok(Some((entry, object)))
+        })
+        .await??;
+        let Some((entry, object)) = entry else {
+            return Ok(None);
+        };
+        dedupe::reuse(Dedupe::Reflink, object, to).await?;
+        Ok(Some(entry))
+    }
+
+    /// 把下载完成的文件存入缓存
+    #[allow(clippy::too_many_arguments)]
+    pub async fn store(
+        &self,
+        from: &Path,
+        url: String,
+        file_name: String,
+        size: u64,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        sha256: Option<[u8; 32]>,
+    ) -> Result<()> {
+        let Some(key) = cache_key(&url, etag.as_deref(), last_modified.as_deref()) else {
+            return Ok(());
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let object = self.object_path(&key);
+        let mut tmp_path = object.as_os_str().to_owned();
+        tmp_path.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = PathBuf::from(tmp_path);
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        dedupe::reuse(Dedupe::Reflink, from.to_path_buf(), tmp_path.clone()).await?;
+        tokio::fs::rename(&tmp_path, &object).await?;
+        let now = fmt::unix_now();
+        self.write_meta(&CacheEntry {
+            key,
+            url,
+            file_name,
+            etag,
+            last_modified,
+            size,
+            sha256: sha256.map(|h| to_hex(&h)),
+            created_at: now,
+            last_used: now,
+        })
+    }
+
+    pub fn remove(&self, key: &str) -> io::Result<()> {
+        for path in [self.meta_path(key), self.object_path(key)] {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 按最近使用时间从旧到新删除缓存项, 直到总大小不超过 `max_size`, 返回删除的缓存项
+    pub fn prune_to(&self, max_size: u64) -> Result<Vec<CacheEntry>> {
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|e| e.last_used);
+        let victims = lru_victims(&entries, max_size);
+        let mut removed = Vec::with_capacity(victims);
+        for entry in entries.into_iter().take(victims) {
+            self.remove(&entry.key)?;
+            removed.push(entry);
+        }
+        Ok(removed)
+    }
+}
+
+/// 已按最近使用时间排序的缓存项中, 需要从头删除多少项才能让总大小不超过 `max_size`
+fn lru_victims(entries: &[CacheEntry], max_size: u64) -> usize {
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    let mut count = 0;
+    for entry in entries {
+        if total <= max_size {
+            break;
+        }
+        total -= entry.size;
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        let url = "https://example.com/a.bin";
+        let by_etag = cache_key(url, Some("\"abc\""), None).unwrap();
+        assert_eq!(by_etag.len(), 64);
+        assert_ne!(
+            Some(&by_etag),
+            cache_key(url, Some("\"abd\""), None).as_ref()
+        );
+        assert_eq!(
+            cache_key(url, Some("W/\"abc\""), Some("date")),
+            cache_key(url, None, Some("date"))
+        );
+        assert_eq!(cache_key(url, Some("W/\"abc\""), None), None);
+    }
+
+    #[test]
+    fn test_lru_victims() {
+        let entry = |size| CacheEntry {
+            key: String::new(),
+            url: String::new(),
+            file_name: String::new(),
+            etag: None,
+            last_modified: None,
+            size,
+            sha256: None,
+            created_at: 0,
+            last_used: 0,
+        };
+        let entries = [entry(10), entry(20), entry(30)];
+        assert_eq!(lru_victims(&entries, 60), 0);
+        assert_eq!(lru_victims(&entries, 50), 1);
+        assert_eq!(lru_victims(&entries, 30), 2);
+        assert_eq!(lru_victims(&entries, 0), 3);
+    }
+
+    #[test]
+    fn test_entry_sha256() {
+        let mut entry = CacheEntry {
+            key: String::new(),
+            url: String::new(),
+            file_name: String::new(),
+            etag: None,
+            last_modified: None,
+            size: 0,
+            sha256: Some(to_hex(&[0xab; 32])),
+            created_at: 0,
+            last_used: 0,
+        };
+        assert_eq!(entry.sha256(), Some([0xab; 32]));
+        entry.sha256 = Some("ab".to_string());
+        assert_eq!(entry.sha256(), None);
+    }
+}
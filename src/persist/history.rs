@@ -0,0 +1,73 @@
+//! 下载历史
+//!
+//! 每次下载结束 (完成、失败或取消) 时向 `history.jsonl` 追加一行记录,
+//! 清除下载记录不会影响历史。
+
+use color_eyre::Result;
+use fs4::fs_std::FileExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+pub(super) const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub file_name: String,
+    pub path: String,
+    /// 已隐藏签名的最终链接
+    pub url: String,
+    pub host: String,
+    /// 文件大小, 未知时为 0
+    pub size: u64,
+    /// 开始时间, Unix 时间戳
+    pub started_at: u64,
+    /// 结束时间, Unix 时间戳
+    pub finished_at: u64,
+    /// 本次下载用时, 单位: ms
+    pub elapsed: u64,
+    /// 本次下载接收的字节数, 包含重复下载的部分
+    pub downloaded: u64,
+    /// 因重试或分块重叠而重复下载的字节数
+    pub redownloaded: u64,
+    /// 下载出错后重试的次数
+    pub retries: u64,
+    /// 平均速度, 单位: B/s
+    pub speed: f64,
+    /// 是否从之前的进度继续下载
+    pub resumed: bool,
+    pub outcome: Outcome,
+}
+
+pub(super) fn append(file_path: &Path, record: &HistoryRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = File::options().create(true).append(true).open(file_path)?;
+    file.lock_exclusive()?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// 读取所有历史记录, 跳过无法解析的行
+pub(super) fn read(file_path: &Path) -> Result<Vec<HistoryRecord>> {
+    let text = match fs::read_to_string(file_path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
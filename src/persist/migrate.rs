@@ -0,0 +1,195 @@
+//! 数据库文件格式与版本迁移
+//!
+//! 文件以 `FDDB` 开头, 之后是 2 字节的小端版本号和 2 字节保留位, 再之后是 rkyv 数据。
+//! 第 1 版没有文件头, 整个文件都是 rkyv 数据。
+//!
+//! 修改 [`DatabaseEntry`] 时, 把旧的结构体原样复制为新的 `vN` 模块,
+//! 增加 [`DB_VERSION`], 并在 [`decode`] 中补上从旧版本升级的转换。
+//!
+//! `fixtures` 目录下的测试数据由各版本的程序实际下载后写入, 生成方法见其中的 `README.md`。
+
+use super::{ArchivedDatabaseInner, DB_VERSION, DatabaseEntry, DatabaseInner};
+use color_eyre::Result;
+use rkyv::{rancor::Error, util::AlignedVec};
+
+const MAGIC: &[u8; 4] = b"FDDB";
+const HEADER_LEN: usize = 8;
+
+/// 第 1 版的数据结构, 不可修改
+mod v1 {
+    use fast_pull::ProgressEntry;
+    use rkyv::{Archive, Deserialize, Serialize};
+
+    #[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct DatabaseEntry {
+        pub file_path: Vec<u8>,
+        pub file_name: String,
+        pub file_size: u64,
+        pub etag: Option<String>,
+        pub last_modified: Option<String>,
+        pub progress: Vec<ProgressEntry>,
+        pub elapsed: u64,
+        pub url: String,
+    }
+
+    #[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct DatabaseInner(pub u16, pub Vec<DatabaseEntry>);
+}
+
+fn from_v1(data: v1::DatabaseInner) -> DatabaseInner {
+    let entries = data
+        .1
+        .into_iter()
+        .map(|e| DatabaseEntry {
+            file_path: e.file_path,
+            file_name: e.file_name,
+            file_size: e.file_size,
+            etag: e.etag,
+            last_modified: e.last_modified,
+            progress: e.progress,
+            elapsed: e.elapsed,
+            url: e.url,
+            options: None,
+            sha256: None,
+        })
+        .collect();
+    DatabaseInner(DB_VERSION, entries)
+}
+
+/// 读取文件中的版本号和数据部分
+fn split_header(bytes: &[u8]) -> (u16, &[u8]) {
+    match bytes.strip_prefix(MAGIC) {
+        Some(rest) if bytes.len() >= HEADER_LEN => {
+            (u16::from_le_bytes([rest[0], rest[1]]), &bytes[HEADER_LEN..])
+        }
+        _ => (1, bytes),
+    }
+}
+
+/// 解析任意已知版本的数据库文件, 返回升级后的数据和文件原本的版本号
+///
+/// 文件来自更新的版本时返回 `None`
+pub(super) fn decode(bytes: &[u8]) -> Result<Option<(DatabaseInner, u16)>> {
+    let (version, payload) = split_header(bytes);
+    let mut aligned = AlignedVec::<16>::with_capacity(payload.len());
+    aligned.extend_from_slice(payload);
+    let data = match version {
+        1 => from_v1(rkyv::from_bytes::<v1::DatabaseInner, Error>(&aligned)?),
+        DB_VERSION => {
+            let archived = rkyv::access::<ArchivedDatabaseInner, Error>(&aligned)?;
+            rkyv::deserialize::<_, Error>(archived)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some((data, version)))
+}
+
+pub(super) fn encode(data: &DatabaseInner) -> Result<Vec<u8>> {
+    let payload = rkyv::to_bytes::<Error>(data)?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&DB_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&[0; HEADER_LEN - MAGIC.len() - 2]);
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// 测试数据中的两条记录: 一个已完成的下载和一个 4 线程下载到一半中断的下载
+    #[allow(clippy::single_range_in_vec_init)]
+    fn expected_v1() -> Vec<DatabaseEntry> {
+        vec![
+            DatabaseEntry {
+                file_path: b"/tmp/fdfx/dl/done.bin".to_vec(),
+                file_name: "big.bin".to_string(),
+                file_size: 5000000,
+                etag: Some("\"6ad5b98f-4c4b40\"".to_string()),
+                last_modified: Some("Mon, 19 Oct 2026 06:32:47 GMT".to_string()),
+                progress: vec![0..5000000],
+                elapsed: 67,
+                url: "http://127.0.0.1:8765/big.bin?token=abc".to_string(),
+                options: None,
+                sha256: None,
+            },
+            DatabaseEntry {
+                file_path: b"/tmp/fdfx/dl/part.bin".to_vec(),
+                file_name: "big.bin".to_string(),
+                file_size: 5000000,
+                etag: Some("\"6ad5b98f-4c4b40\"".to_string()),
+                last_modified: Some("Mon, 19 Oct 2026 06:32:47 GMT".to_string()),
+                progress: vec![
+                    0..262144,
+                    1250000..1512144,
+                    2500000..2762144,
+                    3750000..4012144,
+                ],
+                elapsed: 979,
+                url: "http://127.0.0.1:8767/big.bin".to_string(),
+                options: None,
+                sha256: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_decode_v1() {
+        let (data, version) = decode(include_bytes!("fixtures/state-v1.fd"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(data.0, DB_VERSION);
+        assert_eq!(data.1, expected_v1());
+    }
+
+    #[test]
+    fn test_decode_v2() {
+        let (data, version) = decode(include_bytes!("fixtures/state-v2.fd"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(version, 2);
+        let [done, part] = &data.1[..] else {
+            panic!("{:?}", data.1);
+        };
+        assert_eq!(done.path(), Path::new("/tmp/fdfx2/dl/done.bin"));
+        assert_eq!(done.url, "http://127.0.0.1:8765/big.bin?token=abc");
+        assert!(done.is_finished());
+        let sha256: String = done
+            .sha256
+            .unwrap()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert_eq!(
+            sha256,
+            "440feea8120682a96ef3849458c22d8299a3287292398cc2d813c179e90322c0"
+        );
+        assert_eq!(part.path(), Path::new("/tmp/fdfx2/dl/part.bin.fdpart"));
+        assert_eq!(part.progress, expected_v1()[1].progress);
+        assert_eq!(part.sha256, None);
+        let options = part.options.as_ref().unwrap();
+        assert_eq!(options.threads, 4);
+        assert_eq!(options.writer, "mmap");
+        assert_eq!(options.file_allocation, "sparse");
+        assert_eq!(options.part_suffix, ".fdpart");
+        assert!(options.headers.iter().any(|(name, _)| name == "user-agent"));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let data = DatabaseInner(DB_VERSION, expected_v1());
+        let (decoded, version) = decode(&encode(&data).unwrap()).unwrap().unwrap();
+        assert_eq!(version, DB_VERSION);
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_reject_newer_version() {
+        let mut bytes = encode(&DatabaseInner(DB_VERSION, expected_v1())).unwrap();
+        bytes[4..6].copy_from_slice(&(DB_VERSION + 1).to_le_bytes());
+        assert!(decode(&bytes).unwrap().is_none());
+    }
+}
@@ -0,0 +1,490 @@
+use crate::redact::Redactor;
+use color_eyre::{Result, eyre::eyre};
+use fast_pull::{ProgressEntry, Total};
+use fs4::fs_std::FileExt;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::{
+    env,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+use tokio::{fs, sync::Mutex};
+
+mod cache;
+mod history;
+mod migrate;
+mod sidecar;
+
+pub use cache::Cache;
+pub use history::{HistoryRecord, Outcome};
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DatabaseEntry {
+    pub file_path: Vec<u8>,
+    pub file_name: String,
+    pub file_size: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub progress: Vec<ProgressEntry>,
+    pub elapsed: u64,
+    pub url: String,
+    /// 发起下载时使用的选项, 用于继续下载时还原请求
+    pub options: Option<SavedOptions>,
+    /// 下载完成后计算的 SHA-256, 用于复用文件前校验
+    pub sha256: Option<[u8; 32]>,
+}
+
+/// 与下载记录一起保存的选项, 不包含凭据等敏感信息
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SavedOptions {
+    /// 请求头名称和值
+    pub headers: Vec<(String, Vec<u8>)>,
+    /// 已去除密码的代理地址
+    pub proxy: Option<String>,
+    pub threads: u64,
+    pub writer: String,
+    pub file_allocation: String,
+    pub write_buffer_size: u64,
+    pub write_queue_cap: u64,
+    pub part_suffix: String,
+    pub load_cookies: Option<String>,
+    pub save_cookies: Option<String>,
+    pub browser: bool,
+    pub multiplexing: bool,
+    pub accept_invalid_certs: bool,
+    pub accept_invalid_hostnames: bool,
+}
+
+impl DatabaseEntry {
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(unsafe { OsStr::from_encoded_bytes_unchecked(&self.file_path) })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.file_size > 0 && self.progress.total() >= self.file_size
+    }
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DatabaseInner(/* version */ u16, Vec<DatabaseEntry>);
+
+#[derive(Debug, Clone)]
+pub struct Database {
+    inner: Arc<Mutex<DatabaseInner>>,
+    db_path: Arc<PathBuf>,
+    /// 写入状态文件时隐藏链接中的签名
+    redactor: Arc<Redactor>,
+}
+
+const DB_VERSION: u16 = 2;
+
+const DB_FILE: &str = "state.fd";
+
+static STATE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 指定下载记录的保存目录, 需在首次打开数据库前调用
+pub fn set_state_dir(dir: PathBuf) {
+    let _ = STATE_DIR.set(dir);
+}
+
+/// 旧版本将数据库保存在可执行文件旁边
+fn legacy_db_path() -> Option<PathBuf> {
+    env::current_exe()
+        .ok()
+        .and_then(|path| path.parent().map(|p| p.join(DB_FILE)))
+}
+
+/// 按平台约定选择默认的状态目录, Linux 下为 `$XDG_STATE_HOME/fast-down`
+fn default_state_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::home_dir().map(|home| home.join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| env::home_dir().map(|home| home.join(".local/state")))
+    };
+    base.map(|base| base.join("fast-down"))
+}
+
+/// 把旧位置的数据库迁移到新位置, 旧位置不可写时保留原文件
+async fn migrate_legacy(db_path: &Path) -> Result<()> {
+    let Some(legacy) = legacy_db_path() else {
+        return Ok(());
+    };
+    if legacy == db_path || db_path.try_exists()? || !legacy.try_exists()? {
+        return Ok(());
+    }
+    if fs::rename(&legacy, db_path).await.is_err() {
+        fs::copy(&legacy, db_path).await?;
+    }
+    eprintln!(
+        "{}",
+        t!(
+            "msg.database-migrated",
+            from = legacy.display(),
+            to = db_path.display()
+        )
+    );
+    Ok(())
+}
+
+/// 读取并升级数据库文件, 来自更新版本时返回 `None`
+fn read_file(file_path: &Path) -> Result<Option<(DatabaseInner, u16)>> {
+    migrate::decode(&std::fs::read(file_path)?)
+}
+
+/// 创建只有当前用户可以读写的文件, 数据库中保存了带签名的原始链接
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// 先写入临时文件再重命名, 避免写入中途崩溃损坏数据库
+///
+/// `durable` 为 `false` 时不调用 fsync, 崩溃后可能丢失最近一次写入
+fn write_atomic(file_path: &Path, data: &DatabaseInner, durable: bool) -> Result<()> {
+    let bytes = migrate::encode(data)?;
+    let mut tmp_path = file_path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = PathBuf::from(tmp_path);
+    let result = (|| {
+        let mut file = create_private(&tmp_path)?;
+        file.write_all(&bytes)?;
+        if durable {
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, file_path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    Ok(result?)
+}
+
+/// 获取数据库的进程间排他锁, 返回的文件关闭时自动释放
+fn lock_file(file_path: &Path) -> Result<File> {
+    let mut lock_path = file_path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    file.lock_exclusive()?;
+    Ok(file)
+}
+
+impl Database {
+    pub async fn new() -> Result<Self> {
+        let db_path = match STATE_DIR.get().cloned().or_else(default_state_dir) {
+            Some(dir) => {
+                fs::create_dir_all(&dir).await?;
+                let db_path = dir.join(DB_FILE);
+                if STATE_DIR.get().is_none()
+                    && let Err(err) = migrate_legacy(&db_path).await
+                {
+                    eprintln!("{}: {:#?}", t!("err.database-migrate"), err);
+                }
+                db_path
+            }
+            None => legacy_db_path().unwrap_or(PathBuf::from(DB_FILE)),
+        };
+        if db_path.try_exists()? {
+            match Self::from_file(&db_path).await {
+                Ok(Some(db)) => return Ok(db),
+                // 来自更新版本的数据库, 不能覆盖
+                Ok(None) => return Err(eyre!("{}", t!("err.database-version"))),
+                Err(err) => {
+                    eprintln!("{}: {:#?}", t!("err.database-load"), err);
+                    // 保留损坏的数据库, 以免被新数据覆盖
+                    let mut backup = db_path.as_os_str().to_owned();
+                    backup.push(".corrupt");
+                    fs::rename(&db_path, &backup).await?;
+                    eprintln!(
+                        "{}",
+                        t!("msg.database-backup", path = Path::new(&backup).display())
+                    );
+                }
+            };
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(DatabaseInner(DB_VERSION, vec![]))),
+            db_path: Arc::new(db_path),
+            redactor: Arc::default(),
+        })
+    }
+
+    pub async fn from_file(file_path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let file_path = file_path.as_ref().to_path_buf();
+        let data = tokio::task::spawn_blocking({
+            let file_path = file_path.clone();
+            move || -> Result<_> {
+                let Some((data, version)) = read_file(&file_path)? else {
+                    return Ok(None);
+                };
+                if version < DB_VERSION {
+                    // 升级前保留一份旧版本的数据库
+                    let mut backup = file_path.as_os_str().to_owned();
+                    backup.push(format!(".v{version}"));
+                    std::fs::copy(&file_path, &backup)?;
+                    let _lock = lock_file(&file_path)?;
+                    write_atomic(&file_path, &data, true)?;
+                    eprintln!(
+                        "{}",
+                        t!(
+                            "msg.database-upgraded",
+                            from = version,
+                            to = DB_VERSION,
+                            path = Path::new(&backup).display()
+                        )
+                    );
+                }
+                Ok(Some(data))
+            }
+        })
+        .await??;
+        Ok(data.map(|data| Self {
+            inner: Arc::new(Mutex::new(data)),
+            db_path: Arc::new(file_path),
+            redactor: Arc::default(),
+        }))
+    }
+
+    /// 使用配置中的查询参数隐藏状态文件中的链接, 默认只隐藏内置的参数
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Arc::new(redactor);
+        self
+    }
+
+    /// 在文件锁保护下, 把修改应用到磁盘上最新的数据并写回
+    ///
+    /// 每次写入前都重新读取, 因此多个进程同时下载时不会覆盖彼此的记录。
+    /// `durable` 为 `false` 时不刷入磁盘, 用于下载过程中的定期保存
+    async fn transact<T: Send + 'static>(
+        &self,
+        durable: bool,
+        f: impl FnOnce(&mut Vec<DatabaseEntry>) -> T + Send + 'static,
+    ) -> Result<T> {
+        let mut inner = self.inner.lock().await;
+        let db_path = self.db_path.clone();
+        let fallback = inner.clone();
+        let (data, value) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let _lock = lock_file(&db_path)?;
+            // 文件不存在时以内存中的数据为准, 无法读取时不能覆盖
+            let mut data = if db_path.try_exists()? {
+                match read_file(&db_path)? {
+                    Some((data, _)) => data,
+                    None => return Err(eyre!("{}", t!("err.database-version"))),
+                }
+            } else {
+                fallback
+            };
+            let value = f(&mut data.1);
+            write_atomic(&db_path, &data, durable)?;
+            Ok((data, value))
+        })
+        .await??;
+        *inner = data;
+        Ok(value)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn init_entry(
+        &self,
+        file_path: impl AsRef<OsStr>,
+        file_name: String,
+        file_size: u64,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        url: String,
+        options: SavedOptions,
+    ) -> Result<()> {
+        let entry = DatabaseEntry {
+            file_path: file_path.as_ref().as_encoded_bytes().to_vec(),
+            file_name,
+            file_size,
+            etag,
+            last_modified,
+            url,
+            progress: vec![],
+            elapsed: 0,
+            options: Some(options),
+            sha256: None,
+        };
+        self.put_entry(entry).await
+    }
+
+    /// 添加或替换一条记录, 并同步写入状态文件
+    pub async fn put_entry(&self, entry: DatabaseEntry) -> Result<()> {
+        let sidecar_entry = entry.clone();
+        let redactor = self.redactor.clone();
+        self.transact(true, move |entries| {
+            entries.retain(|e| e.file_path != entry.file_path);
+            entries.push(entry);
+        })
+        .await?;
+        tokio::task::spawn_blocking(move || sidecar::sync(&sidecar_entry, &redactor, true)).await?
+    }
+
+    /// 全局数据库中没有记录时, 从下载文件旁的状态文件导入
+    pub async fn import_sidecar(
+        &self,
+        file_path: impl AsRef<OsStr>,
+    ) -> Result<Option<DatabaseEntry>> {
+        let file_path = PathBuf::from(file_path.as_ref());
+        let Some(entry) = tokio::task::spawn_blocking(move || sidecar::read(&file_path)).await??
+        else {
+            return Ok(None);
+        };
+        self.put_entry(entry.clone()).await?;
+        Ok(Some(entry))
+    }
+
+    pub async fn entries(&self) -> Vec<DatabaseEntry> {
+        self.inner.lock().await.1.clone()
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.db_path.with_file_name(history::HISTORY_FILE)
+    }
+
+    /// 追加一条下载历史
+    pub async fn add_history(&self, record: HistoryRecord) -> Result<()> {
+        let history_path = self.history_path();
+        tokio::task::spawn_blocking(move || history::append(&history_path, &record)).await?
+    }
+
+    /// 按时间顺序读取所有下载历史
+    pub async fn history(&self) -> Result<Vec<HistoryRecord>> {
+        let history_path = self.history_path();
+        tokio::task::spawn_blocking(move || history::read(&history_path)).await?
+    }
+
+    pub async fn get_entry(&self, file_path: impl AsRef<OsStr>) -> Option<DatabaseEntry> {
+        self.inner
+            .lock()
+            .await
+            .1
+            .iter()
+            .find(|entry| entry.file_path == file_path.as_ref().as_encoded_bytes())
+            .cloned()
+    }
+
+    /// 修改指定路径的记录, 记录不存在时 (如已被其他进程清除) 不做任何事
+    ///
+    /// `durable` 控制数据库和状态文件是否立即刷入磁盘
+    async fn modify_entry(
+        &self,
+        file_path: impl AsRef<OsStr>,
+        durable: bool,
+        f: impl FnOnce(&mut DatabaseEntry) + Send + 'static,
+    ) -> Result<()> {
+        let file_path = file_path.as_ref().as_encoded_bytes().to_vec();
+        let entry = self
+            .transact(durable, move |entries| {
+                let entry = entries.iter_mut().find(|e| e.file_path == file_path)?;
+                f(entry);
+                Some(entry.clone())
+            })
+            .await?;
+        if let Some(entry) = entry {
+            let redactor = self.redactor.clone();
+            tokio::task::spawn_blocking(move || sidecar::sync(&entry, &redactor, durable))
+                .await??;
+        }
+        Ok(())
+    }
+
+    /// 下载过程中定期保存进度, 数据库和状态文件都不刷入磁盘
+    ///
+    /// 暂停、出错或退出时应调用 [`Self::checkpoint_entry`]
+    pub async fn update_entry(
+        &self,
+        file_path: impl AsRef<OsStr>,
+        progress: Vec<ProgressEntry>,
+        elapsed: u64,
+    ) -> Result<()> {
+        self.modify_entry(file_path, false, move |entry| {
+            entry.progress = progress;
+            entry.elapsed = elapsed;
+        })
+        .await
+    }
+
+    /// 保存进度并把数据库和状态文件刷入磁盘
+    pub async fn checkpoint_entry(
+        &self,
+        file_path: impl AsRef<OsStr>,
+        progress: Vec<ProgressEntry>,
+        elapsed: u64,
+    ) -> Result<()> {
+        self.modify_entry(file_path, true, move |entry| {
+            entry.progress = progress;
+            entry.elapsed = elapsed;
+        })
+        .await
+    }
+
+    pub async fn set_file_size(&self, file_path: impl AsRef<OsStr>, file_size: u64) -> Result<()> {
+        self.modify_entry(file_path, true, move |entry| entry.file_size = file_size)
+            .await
+    }
+
+    pub async fn set_sha256(&self, file_path: impl AsRef<OsStr>, sha256: [u8; 32]) -> Result<()> {
+        self.modify_entry(file_path, true, move |entry| entry.sha256 = Some(sha256))
+            .await
+    }
+
+    pub async fn set_url(&self, file_path: impl AsRef<OsStr>, url: String) -> Result<()> {
+        self.modify_entry(file_path, true, move |entry| entry.url = url)
+            .await
+    }
+
+    pub async fn rename_entry(&self, from: impl AsRef<OsStr>, to: impl AsRef<OsStr>) -> Result<()> {
+        let from = from.as_ref().as_encoded_bytes().to_vec();
+        let to = to.as_ref().as_encoded_bytes().to_vec();
+        if from == to {
+            return Ok(());
+        }
+        let sidecar_from = PathBuf::from(unsafe { OsStr::from_encoded_bytes_unchecked(&from) });
+        self.transact(true, move |entries| {
+            entries.retain(|e| e.file_path != to);
+            if let Some(entry) = entries.iter_mut().find(|e| e.file_path == from) {
+                entry.file_path = to;
+            }
+        })
+        .await?;
+        sidecar::remove(&sidecar_from)?;
+        Ok(())
+    }
+
+    /// 删除记录及其状态文件, 否则状态文件之后会被重新导入
+    pub async fn remove_entries(&self, file_paths: &[Vec<u8>]) -> Result<usize> {
+        let removed = {
+            let file_paths = file_paths.to_vec();
+            self.transact(true, move |entries| {
+                let origin_len = entries.len();
+                entries.retain(|e| !file_paths.contains(&e.file_path));
+                origin_len - entries.len()
+            })
+            .await?
+        };
+        for file_path in file_paths {
+            let file_path = unsafe { OsStr::from_encoded_bytes_unchecked(file_path) };
+            sidecar::remove(Path::new(file_path))?;
+        }
+        Ok(removed)
+    }
+}
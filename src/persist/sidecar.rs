@@ -0,0 +1,66 @@
+//! 保存在每个未完成下载旁边的状态文件 (类似 aria2 的 `.aria2`)
+//!
+//! 文件内容与数据库相同, 只包含一条记录。把下载目录复制到其他机器后,
+//! 即使全局数据库中没有记录也能继续下载。
+//!
+//! 状态文件随下载文件一起移动, 因此其中的链接隐藏了签名和密码,
+//! 只从状态文件导入的记录需要用 `fast resume --url` 提供新链接。
+
+use super::{DB_VERSION, DatabaseEntry, DatabaseInner, migrate, write_atomic};
+use crate::redact::Redactor;
+use color_eyre::Result;
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const EXTENSION: &str = ".fdstate";
+
+fn sidecar_path(file_path: &Path) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(EXTENSION);
+    PathBuf::from(path)
+}
+
+pub(super) fn remove(file_path: &Path) -> io::Result<()> {
+    match fs::remove_file(sidecar_path(file_path)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// 下载未完成时写入状态文件, 完成后删除
+pub(super) fn sync(entry: &DatabaseEntry, redactor: &Redactor, durable: bool) -> Result<()> {
+    let file_path = entry.path();
+    if entry.is_finished() {
+        remove(&file_path)?;
+        return Ok(());
+    }
+    write_atomic(
+        &sidecar_path(&file_path),
+        &DatabaseInner(
+            DB_VERSION,
+            vec![DatabaseEntry {
+                url: redactor.url_str(&entry.url),
+                ..entry.clone()
+            }],
+        ),
+        durable,
+    )
+}
+
+/// 读取状态文件, 并把记录中的路径改为文件当前所在的位置
+pub(super) fn read(file_path: &Path) -> Result<Option<DatabaseEntry>> {
+    let path = sidecar_path(file_path);
+    if !path.try_exists()? {
+        return Ok(None);
+    }
+    let Some((data, _)) = migrate::decode(&fs::read(path)?)? else {
+        return Ok(None);
+    };
+    Ok(data.1.into_iter().next().map(|mut entry| {
+        entry.file_path = OsStr::new(file_path).as_encoded_bytes().to_vec();
+        entry
+    }))
+}
@@ -26,6 +26,7 @@ pub struct Painter {
     pub avg_speed: f64,
     pub repaint_duration: Duration,
     pub last_repaint_time: Instant,
+    spinner_tick: usize,
     has_progress: bool,
     stderr: Stderr,
 }
@@ -51,6 +52,7 @@ impl Painter {
             curr_size: init_size,
             avg_speed: 0.0,
             last_repaint_time: Instant::now(),
+            spinner_tick: 0,
             has_progress: false,
             stderr: io::stderr(),
         }
@@ -112,9 +114,9 @@ impl Painter {
         self.avg_speed = self.avg_speed * self.alpha + curr_speed * (1.0 - self.alpha);
         let progress_str = if self.file_size == 0 {
             format!(
-                "|{}| {:>6.2}% ({:>8}/Unknown)\n{}\n",
-                BLOCK_CHARS[0].to_string().repeat(self.width as usize),
-                0.0,
+                "|{}| {:>7} ({:>8}/Unknown)\n{}\n",
+                self.spinner(),
+                "",
                 fmt::format_size(self.curr_size as f64),
                 t!(
                     "progress.desc",
@@ -179,6 +181,24 @@ impl Painter {
         Ok(())
     }
 
+    /// 文件大小未知时, 用来回移动的滑块代替进度条
+    fn spinner(&mut self) -> String {
+        let width = self.width as usize;
+        let len = (width / 8).max(1).min(width);
+        let span = width - len;
+        let pos = if span == 0 {
+            0
+        } else {
+            let tick = self.spinner_tick % (span * 2);
+            if tick > span { span * 2 - tick } else { tick }
+        };
+        self.spinner_tick += 1;
+        let mut bar = BLOCK_CHARS[0].to_string().repeat(pos);
+        bar.extend(std::iter::repeat_n(BLOCK_CHARS[8], len));
+        bar.extend(std::iter::repeat_n(BLOCK_CHARS[0], width - pos - len));
+        bar
+    }
+
     pub fn print(&mut self, msg: &str) -> io::Result<()> {
         self.reset_pos()?;
         self.stderr.queue(Print(msg))?;
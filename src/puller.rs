@@ -1,11 +1,16 @@
+use crate::{cookies::CookieJar, pusher::ReorderWindow};
 use bytes::Bytes;
 use fast_pull::{RandPuller, SeqPuller, reqwest::ReqwestPuller};
-use futures::TryStream;
+use futures::{FutureExt, Stream, StreamExt, TryStream, TryStreamExt, stream::BoxStream};
 use reqwest::{
-    ClientBuilder, Proxy,
-    header::{HeaderMap, HeaderValue},
+    Client, ClientBuilder, Proxy, Response, StatusCode,
+    header::{self, HeaderMap, HeaderValue},
+};
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
 };
-use std::sync::Arc;
 use url::Url;
 
 pub fn build_client(
@@ -13,11 +18,13 @@ pub fn build_client(
     proxy: &Option<String>,
     accept_invalid_certs: bool,
     accept_invalid_hostnames: bool,
+    cookie_jar: &Arc<CookieJar>,
 ) -> Result<reqwest::Client, reqwest::Error> {
     let mut client = ClientBuilder::new()
         .default_headers(headers.clone())
         .danger_accept_invalid_certs(accept_invalid_certs)
         .danger_accept_invalid_hostnames(accept_invalid_hostnames)
+        .cookie_provider(cookie_jar.clone())
         .http2_adaptive_window(true)
         .brotli(true)
         .gzip(true)
@@ -32,9 +39,12 @@ pub fn build_client(
 
 pub struct FastDownPuller {
     inner: ReqwestPuller,
+    client: Client,
+    start: u64,
     headers: Arc<HeaderMap<HeaderValue>>,
     proxy: Arc<Option<String>>,
     url: Arc<Url>,
+    cookie_jar: Arc<CookieJar>,
     multiplexing: bool,
     accept_invalid_certs: bool,
     accept_invalid_hostnames: bool,
@@ -48,23 +58,33 @@ impl FastDownPuller {
         multiplexing: bool,
         accept_invalid_certs: bool,
         accept_invalid_hostnames: bool,
+        cookie_jar: Arc<CookieJar>,
     ) -> Result<Self, reqwest::Error> {
         let client = build_client(
             &headers,
             &proxy,
             accept_invalid_certs,
             accept_invalid_hostnames,
+            &cookie_jar,
         )?;
         Ok(Self {
-            inner: ReqwestPuller::new(url.clone(), client),
+            inner: ReqwestPuller::new(url.clone(), client.clone()),
+            client,
+            start: 0,
             headers: Arc::new(headers),
             proxy: Arc::new(proxy),
             url: Arc::new(url),
+            cookie_jar,
             multiplexing,
             accept_invalid_certs,
             accept_invalid_hostnames,
         })
     }
+
+    /// 设置单线程下载的起始位置, 用于断点续传
+    pub fn set_start(&mut self, start: u64) {
+        self.start = start;
+    }
 }
 
 impl Clone for FastDownPuller {
@@ -72,9 +92,12 @@ impl Clone for FastDownPuller {
         if self.multiplexing {
             Self {
                 inner: self.inner.clone(),
+                client: self.client.clone(),
+                start: self.start,
                 headers: self.headers.clone(),
                 proxy: self.proxy.clone(),
                 url: self.url.clone(),
+                cookie_jar: self.cookie_jar.clone(),
                 multiplexing: self.multiplexing,
                 accept_invalid_certs: self.accept_invalid_certs,
                 accept_invalid_hostnames: self.accept_invalid_hostnames,
@@ -85,13 +108,17 @@ impl Clone for FastDownPuller {
                 &self.proxy,
                 self.accept_invalid_certs,
                 self.accept_invalid_hostnames,
+                &self.cookie_jar,
             )
             .unwrap();
             Self {
-                inner: ReqwestPuller::new(self.url.as_ref().clone(), client),
+                inner: ReqwestPuller::new(self.url.as_ref().clone(), client.clone()),
+                client,
+                start: self.start,
                 headers: self.headers.clone(),
                 proxy: self.proxy.clone(),
                 url: self.url.clone(),
+                cookie_jar: self.cookie_jar.clone(),
                 multiplexing: self.multiplexing,
                 accept_invalid_certs: self.accept_invalid_certs,
                 accept_invalid_hostnames: self.accept_invalid_hostnames,
@@ -113,6 +140,126 @@ impl RandPuller for FastDownPuller {
 impl SeqPuller for FastDownPuller {
     type Error = reqwest::Error;
     fn pull(&mut self) -> impl TryStream<Ok = Bytes, Error = Self::Error> + Send + Unpin {
-        SeqPuller::pull(&mut self.inner)
+        SeqStream {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            pos: self.start,
+            state: SeqState::None,
+        }
+    }
+}
+
+type ResponseFut = Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>>;
+
+enum SeqState {
+    None,
+    Pending(ResponseFut),
+    Ready(BoxStream<'static, reqwest::Result<Bytes>>, u64),
+    Done,
+}
+
+/// 单线程下载流, 连接中断后用 `Range: bytes=N-` 从已接收的位置继续
+///
+/// 服务端忽略 Range 返回完整内容时, 丢弃已经接收过的部分
+struct SeqStream {
+    client: Client,
+    url: Arc<Url>,
+    pos: u64,
+    state: SeqState,
+}
+
+/// 响应内容在文件中的起始位置
+fn response_start(resp: &Response) -> u64 {
+    if resp.status() != StatusCode::PARTIAL_CONTENT {
+        return 0;
+    }
+    resp.headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes "))
+        .and_then(|v| v.split('-').next())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+impl Stream for SeqStream {
+    type Item = reqwest::Result<Bytes>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match &mut self.state {
+                SeqState::None => {
+                    let mut req = self.client.get(self.url.as_ref().clone());
+                    if self.pos > 0 {
+                        req = req.header(header::RANGE, format!("bytes={}-", self.pos));
+                    }
+                    self.state = SeqState::Pending(Box::pin(req.send()));
+                }
+                SeqState::Pending(resp) => {
+                    match ready!(resp.poll_unpin(cx)).and_then(Response::error_for_status) {
+                        Ok(resp) => {
+                            let skip = self.pos.saturating_sub(response_start(&resp));
+                            self.state = SeqState::Ready(resp.bytes_stream().boxed(), skip);
+                        }
+                        Err(e) => {
+                            self.state = SeqState::None;
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                }
+                SeqState::Ready(stream, skip) => match ready!(stream.poll_next_unpin(cx)) {
+                    Some(Ok(mut chunk)) => {
+                        if (chunk.len() as u64) <= *skip {
+                            *skip -= chunk.len() as u64;
+                            continue;
+                        }
+                        let chunk = chunk.split_off(*skip as usize);
+                        *skip = 0;
+                        self.pos += chunk.len() as u64;
+                        return Poll::Ready(Some(Ok(chunk)));
+                    }
+                    Some(Err(e)) => {
+                        self.state = SeqState::None;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    None => self.state = SeqState::Done,
+                },
+                SeqState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// 在拉取数据前等待重排窗口, 避免乱序数据无限堆积在内存中
+#[derive(Clone)]
+pub struct WindowedPuller<P> {
+    inner: P,
+    window: Arc<ReorderWindow>,
+}
+
+impl<P> WindowedPuller<P> {
+    pub fn new(inner: P, window: Arc<ReorderWindow>) -> Self {
+        Self { inner, window }
+    }
+}
+
+impl<P: RandPuller + Sync> RandPuller for WindowedPuller<P> {
+    type Error = P::Error;
+    fn pull(
+        &mut self,
+        range: &fast_pull::ProgressEntry,
+    ) -> impl TryStream<Ok = Bytes, Error = Self::Error> + Send + Unpin {
+        let window = self.window.clone();
+        let mut pos = range.start;
+        Box::pin(
+            RandPuller::pull(&mut self.inner, range).and_then(move |chunk| {
+                let start = pos;
+                pos += chunk.len() as u64;
+                let window = window.clone();
+                async move {
+                    window.wait(start).await;
+                    Ok(chunk)
+                }
+            }),
+        )
     }
 }
@@ -0,0 +1,71 @@
+use clap::ValueEnum;
+use fs4::fs_std::FileExt;
+use std::{fs, path::Path};
+use tokio::{
+    fs::OpenOptions,
+    io::{self, AsyncSeekExt, AsyncWriteExt, SeekFrom},
+    task,
+};
+
+/// 文件空间分配方式
+///
+/// pwrite 和 direct 写入方式支持所有分配方式; mmap 写入方式映射文件前总会把文件设为完整长度,
+/// 因此不支持 `None`, 其余方式在映射前生效
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileAllocation {
+    /// 不预先分配空间 (不能与 mmap 写入方式一起使用)
+    None,
+    /// 只设置文件长度, 生成稀疏文件
+    #[default]
+    Sparse,
+    /// 写入 0 占满文件空间
+    Prealloc,
+    /// 使用 fallocate 分配空间
+    Falloc,
+}
+
+/// 按照分配方式预先为文件分配 `size` 字节, 只会扩展文件, 不会覆盖已有数据
+pub async fn allocate(path: &Path, size: u64, allocation: FileAllocation) -> io::Result<()> {
+    static ZEROS: [u8; 64 * 1024] = [0; 64 * 1024];
+    match allocation {
+        FileAllocation::None => Ok(()),
+        FileAllocation::Sparse => {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(path)
+                .await?;
+            if file.metadata().await?.len() < size {
+                file.set_len(size).await?;
+            }
+            Ok(())
+        }
+        FileAllocation::Prealloc => {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(path)
+                .await?;
+            let mut len = file.metadata().await?.len();
+            file.seek(SeekFrom::Start(len)).await?;
+            while len < size {
+                let n = (size - len).min(ZEROS.len() as u64) as usize;
+                file.write_all(&ZEROS[..n]).await?;
+                len += n as u64;
+            }
+            file.flush().await
+        }
+        FileAllocation::Falloc => {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(path)?;
+            task::spawn_blocking(move || file.allocate(size))
+                .await
+                .map_err(io::Error::other)?
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use super::pwrite::{read_at, write_all_at};
+use bytes::Bytes;
+use fast_pull::{MergeProgress, ProgressEntry, RandPusher, Total, file::FilePusherError};
+use std::{collections::BTreeMap, fs::File, path::Path, sync::Arc};
+use tokio::{io, task};
+
+/// O_DIRECT 要求缓冲区, 偏移和长度都按块对齐
+const ALIGN: usize = 4096;
+
+#[derive(Debug)]
+struct AlignedBuf {
+    raw: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn zeroed(len: usize) -> Self {
+        let raw = vec![0; len + ALIGN];
+        let offset = raw.as_ptr().align_offset(ALIGN);
+        Self { raw, offset, len }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.raw[self.offset..self.offset + self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.raw[self.offset..self.offset + self.len]
+    }
+}
+
+#[derive(Debug)]
+struct Block {
+    buf: AlignedBuf,
+    filled: Vec<ProgressEntry>,
+}
+
+fn open_direct(path: &Path) -> io::Result<File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(false);
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_DIRECT);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_FLAG_NO_BUFFERING: u32 = 0x20000000;
+        options.custom_flags(FILE_FLAG_NO_BUFFERING);
+    }
+    let file = options.open(path)?;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    {
+        use std::os::fd::AsRawFd;
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(file)
+}
+
+/// 绕过页缓存直接写盘, 适合不希望污染页缓存的超大文件
+///
+/// 数据先按块在内存中拼接, 块被填满后才整块写入; 未填满的块在 flush 时先读出磁盘上的原数据再合并写回
+#[derive(Debug)]
+pub struct RandFilePusherDirect {
+    file: Arc<File>,
+    size: u64,
+    block_size: u64,
+    blocks: BTreeMap<u64, Block>,
+    absorbed: Option<ProgressEntry>,
+}
+
+impl RandFilePusherDirect {
+    pub async fn new(
+        path: impl AsRef<Path>,
+        size: u64,
+        buffer_size: usize,
+    ) -> Result<Self, FilePusherError> {
+        let path = path.as_ref().to_path_buf();
+        let file = task::spawn_blocking(move || open_direct(&path))
+            .await
+            .map_err(io::Error::other)??;
+        Ok(Self {
+            file: Arc::new(file),
+            size,
+            block_size: (buffer_size / ALIGN).max(1) as u64 * ALIGN as u64,
+            blocks: BTreeMap::new(),
+            absorbed: None,
+        })
+    }
+
+    fn block_len(&self, index: u64) -> u64 {
+        self.block_size.min(self.size - index * self.block_size)
+    }
+
+    fn absorb(&mut self, range: &ProgressEntry, content: &[u8]) {
+        let mut pos = range.start;
+        let mut content = content;
+        while !content.is_empty() {
+            let index = pos / self.block_size;
+            let offset = pos - index * self.block_size;
+            let len = (content.len() as u64).min(self.block_len(index) - offset);
+            let block_size = self.block_size as usize;
+            let block = self.blocks.entry(index).or_insert_with(|| Block {
+                buf: AlignedBuf::zeroed(block_size),
+                filled: Vec::new(),
+            });
+            let (head, tail) = content.split_at(len as usize);
+            block.buf.as_mut_slice()[offset as usize..(offset + len) as usize]
+                .copy_from_slice(head);
+            block.filled.merge_progress(offset..offset + len);
+            pos += len;
+            content = tail;
+        }
+    }
+
+    async fn write_blocks(&mut self, indexes: Vec<u64>, merge: bool) -> io::Result<()> {
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let blocks: Vec<_> = indexes
+            .into_iter()
+            .map(|i| (i, self.block_len(i), self.blocks.remove(&i).unwrap()))
+            .collect();
+        let file = self.file.clone();
+        let block_size = self.block_size;
+        let (blocks, res) = task::spawn_blocking(move || {
+            let res = blocks.iter().try_for_each(|(index, len, block)| {
+                let offset = index * block_size;
+                let aligned_len = (*len as usize).next_multiple_of(ALIGN);
+                if !merge || block.filled.total() == *len {
+                    return write_all_at(&file, &block.buf.as_slice()[..aligned_len], offset);
+                }
+                let mut disk = AlignedBuf::zeroed(aligned_len);
+                read_at(&file, disk.as_mut_slice(), offset)?;
+                for range in &block.filled {
+                    let range = range.start as usize..range.end as usize;
+                    disk.as_mut_slice()[range.clone()]
+                        .copy_from_slice(&block.buf.as_slice()[range]);
+                }
+                write_all_at(&file, disk.as_slice(), offset)
+            });
+            (blocks, res)
+        })
+        .await
+        .map_err(io::Error::other)?;
+        if res.is_err() {
+            self.blocks
+                .extend(blocks.into_iter().map(|(index, _, block)| (index, block)));
+        }
+        res
+    }
+}
+
+impl RandPusher for RandFilePusherDirect {
+    type Error = FilePusherError;
+    async fn push(&mut self, range: ProgressEntry, content: Bytes) -> Result<(), Self::Error> {
+        // 写入失败时会以同样的参数重试, 不能重复拼接
+        if self.absorbed.as_ref() != Some(&range) {
+            self.absorb(&range, &content);
+            self.absorbed = Some(range);
+        }
+        let full = self
+            .blocks
+            .iter()
+            .filter(|(index, block)| block.filled.total() == self.block_len(**index))
+            .map(|(index, _)| *index)
+            .collect();
+        Ok(self.write_blocks(full, false).await?)
+    }
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let rest = self.blocks.keys().copied().collect();
+        self.write_blocks(rest, true).await?;
+        let file = self.file.clone();
+        let size = self.size;
+        task::spawn_blocking(move || {
+            if file.metadata()?.len() > size {
+                file.set_len(size)?;
+            }
+            file.sync_data()
+        })
+        .await
+        .map_err(io::Error::other)??;
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+mod allocation;
+mod direct;
+mod pwrite;
+mod stdout;
+mod writer;
+
+pub use allocation::*;
+pub use direct::*;
+pub use pwrite::*;
+pub use stdout::*;
+pub use writer::*;
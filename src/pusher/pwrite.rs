@@ -0,0 +1,110 @@
+use bytes::Bytes;
+use fast_pull::{ProgressEntry, RandPusher, file::FilePusherError};
+use std::{fs::File, path::Path, sync::Arc};
+use tokio::{io, task};
+
+#[cfg(unix)]
+pub(crate) fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_write(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// 尽量读满 `buf`, 返回实际读取的字节数, 小于 `buf.len()` 说明读到了文件末尾
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    #[cfg(unix)]
+    use std::os::unix::fs::FileExt;
+    #[cfg(windows)]
+    use std::os::windows::fs::FileExt;
+    let mut read = 0;
+    while read < buf.len() {
+        #[cfg(unix)]
+        let res = file.read_at(&mut buf[read..], offset + read as u64);
+        #[cfg(windows)]
+        let res = file.seek_read(&mut buf[read..], offset + read as u64);
+        match res {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// 使用 pwrite 按偏移写入, 不依赖 mmap, 也不会修改文件长度
+#[derive(Debug)]
+pub struct RandFilePusherPwrite {
+    file: Arc<File>,
+    cache: Vec<(u64, Bytes)>,
+    cache_size: usize,
+    buffer_size: usize,
+}
+
+impl RandFilePusherPwrite {
+    pub async fn new(path: impl AsRef<Path>, buffer_size: usize) -> Result<Self, FilePusherError> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)
+            .await?
+            .into_std()
+            .await;
+        Ok(Self {
+            file: Arc::new(file),
+            cache: Vec::new(),
+            cache_size: 0,
+            buffer_size,
+        })
+    }
+}
+
+impl RandPusher for RandFilePusherPwrite {
+    type Error = FilePusherError;
+    async fn push(&mut self, range: ProgressEntry, content: Bytes) -> Result<(), Self::Error> {
+        self.cache_size += content.len();
+        self.cache.push((range.start, content));
+        if self.cache_size >= self.buffer_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let file = self.file.clone();
+        let cache = std::mem::take(&mut self.cache);
+        let (cache, res) = task::spawn_blocking(move || {
+            let res = cache
+                .iter()
+                .try_for_each(|(start, content)| write_all_at(&file, content, *start));
+            (cache, res)
+        })
+        .await
+        .map_err(io::Error::other)?;
+        match res {
+            Ok(()) => {
+                self.cache_size = 0;
+                Ok(())
+            }
+            Err(e) => {
+                self.cache = cache;
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use bytes::Bytes;
+use fast_pull::{ProgressEntry, RandPusher, SeqPusher, file::FilePusherError};
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::{
+    io::{self, AsyncWriteExt, BufWriter, Stdout},
+    sync::Notify,
+};
+
+/// 输出到 stdout 时的重排窗口, 限制乱序数据最多领先已输出位置 `budget` 字节
+#[derive(Debug)]
+pub struct ReorderWindow {
+    emitted: AtomicU64,
+    budget: u64,
+    notify: Notify,
+}
+
+impl ReorderWindow {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            emitted: AtomicU64::new(0),
+            budget: budget.max(1),
+            notify: Notify::new(),
+        }
+    }
+
+    /// 等待直到从 `pos` 开始的数据落入窗口内
+    pub async fn wait(&self, pos: u64) {
+        loop {
+            let mut notified = pin!(self.notify.notified());
+            notified.as_mut().enable();
+            if pos < self.emitted.load(Ordering::Acquire) + self.budget {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn advance(&self, emitted: u64) {
+        self.emitted.store(emitted, Ordering::Release);
+        self.notify.notify_waiters();
+    }
+}
+
+#[derive(Debug)]
+pub struct StdoutSeqPusher {
+    stdout: BufWriter<Stdout>,
+}
+
+impl StdoutSeqPusher {
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            stdout: BufWriter::with_capacity(buffer_size, io::stdout()),
+        }
+    }
+}
+
+impl SeqPusher for StdoutSeqPusher {
+    type Error = FilePusherError;
+    async fn push(&mut self, content: Bytes) -> Result<(), Self::Error> {
+        Ok(self.stdout.write_all(&content).await?)
+    }
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(self.stdout.flush().await?)
+    }
+}
+
+/// 缓存乱序到达的数据块, 按位置顺序取出连续的部分
+///
+/// 重试或重新拆分的数据块可能与已输出的部分重叠, 重叠部分会被丢弃
+#[derive(Debug, Default)]
+struct Reorder {
+    pending: BTreeMap<u64, Bytes>,
+    emitted: u64,
+}
+
+impl Reorder {
+    fn insert(&mut self, start: u64, content: Bytes) {
+        let end = start + content.len() as u64;
+        if end <= self.emitted {
+            return;
+        }
+        let trimmed = start.max(self.emitted);
+        let content = content.slice((trimmed - start) as usize..);
+        // 同一位置保留更长的数据块
+        match self.pending.entry(trimmed) {
+            Entry::Occupied(entry) if entry.get().len() >= content.len() => {}
+            entry => {
+                entry.insert_entry(content);
+            }
+        }
+    }
+
+    /// 取出下一段紧接已输出位置的数据
+    fn pop(&mut self) -> Option<Bytes> {
+        while let Some(entry) = self.pending.first_entry()
+            && *entry.key() <= self.emitted
+        {
+            let (start, content) = entry.remove_entry();
+            let skip = (self.emitted - start) as usize;
+            if skip < content.len() {
+                self.emitted = start + content.len() as u64;
+                return Some(content.slice(skip..));
+            }
+        }
+        None
+    }
+}
+
+/// 多线程下载到 stdout, 缓存乱序到达的数据块, 只按顺序输出连续的部分
+#[derive(Debug)]
+pub struct StdoutRandPusher {
+    stdout: BufWriter<Stdout>,
+    reorder: Reorder,
+    window: Arc<ReorderWindow>,
+}
+
+impl StdoutRandPusher {
+    pub fn new(window: Arc<ReorderWindow>) -> Self {
+        Self {
+            stdout: BufWriter::new(io::stdout()),
+            reorder: Reorder::default(),
+            window,
+        }
+    }
+}
+
+impl RandPusher for StdoutRandPusher {
+    type Error = FilePusherError;
+    async fn push(&mut self, range: ProgressEntry, content: Bytes) -> Result<(), Self::Error> {
+        self.reorder.insert(range.start, content);
+        while let Some(content) = self.reorder.pop() {
+            self.stdout.write_all(&content).await?;
+        }
+        self.window.advance(self.reorder.emitted);
+        Ok(())
+    }
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(self.stdout.flush().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(reorder: &mut Reorder) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Some(content) = reorder.pop() {
+            out.extend_from_slice(&content);
+        }
+        out
+    }
+
+    #[test]
+    fn test_reorder() {
+        let mut reorder = Reorder::default();
+        reorder.insert(4, Bytes::from_static(b"efgh"));
+        assert_eq!(drain(&mut reorder), b"");
+        reorder.insert(0, Bytes::from_static(b"abcd"));
+        assert_eq!(drain(&mut reorder), b"abcdefgh");
+
+        // 重试的数据块与已输出的部分重叠
+        reorder.insert(6, Bytes::from_static(b"ghij"));
+        assert_eq!(drain(&mut reorder), b"ij");
+        reorder.insert(2, Bytes::from_static(b"cdef"));
+        assert_eq!(drain(&mut reorder), b"");
+
+        // 乱序到达且互相重叠的数据块
+        reorder.insert(14, Bytes::from_static(b"op"));
+        reorder.insert(12, Bytes::from_static(b"mnop"));
+        reorder.insert(9, Bytes::from_static(b"jklm"));
+        assert_eq!(drain(&mut reorder), b"klmnop");
+        assert!(reorder.pending.is_empty());
+        assert_eq!(reorder.emitted, 16);
+    }
+}
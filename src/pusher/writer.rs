@@ -0,0 +1,25 @@
+use clap::ValueEnum;
+
+/// 多线程下载时的写入方式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileWriter {
+    /// 内存映射写入 (仅 64 位系统, 32 位系统的地址空间不足以映射大文件)
+    #[cfg(target_pointer_width = "64")]
+    Mmap,
+    /// 按偏移写入 (pwrite)
+    Pwrite,
+    /// 绕过页缓存直接写盘 (O_DIRECT)
+    Direct,
+}
+
+impl Default for FileWriter {
+    #[cfg(target_pointer_width = "64")]
+    fn default() -> Self {
+        Self::Mmap
+    }
+
+    #[cfg(not(target_pointer_width = "64"))]
+    fn default() -> Self {
+        Self::Pwrite
+    }
+}
@@ -0,0 +1,39 @@
+use color_eyre::Result;
+use std::future::Future;
+
+/// 依次执行多个下载任务并输出每个任务的结果, 单个任务失败不影响后续任务
+pub async fn run<T, F, Fut>(tasks: Vec<T>, mut f: F)
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let total = tasks.len();
+    eprintln!("{}", t!("msg.find-tasks", count = total));
+    let mut success = 0;
+    for (i, task) in tasks.into_iter().enumerate() {
+        let id = i + 1;
+        eprintln!("{}", t!("msg.start-tasks", id = id, total = total));
+        match f(task).await {
+            Ok(()) => {
+                success += 1;
+                eprintln!("{}", t!("msg.finish-tasks", id = id, total = total));
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}\n{:?}",
+                    t!("msg.error-tasks", id = id, total = total),
+                    err
+                );
+            }
+        }
+    }
+    eprintln!(
+        "{}",
+        t!(
+            "msg.finish-all-tasks",
+            total = total,
+            success = success,
+            failed = total - success
+        )
+    );
+}
@@ -0,0 +1,40 @@
+use crate::persist::Database;
+use fast_pull::UrlInfo;
+use std::{fs::File, io, path::Path, time::SystemTime};
+
+/// 判断本地文件与远端是否一致
+///
+/// 优先使用数据库中记录的 ETag 和 Last-Modified, 没有记录时比较文件大小和修改时间
+pub async fn is_unchanged(db: &Database, path: &Path, info: &UrlInfo) -> io::Result<bool> {
+    if let Some(entry) = db.get_entry(path).await {
+        #[allow(clippy::single_range_in_vec_init)]
+        if entry.progress != [0..entry.file_size] || entry.file_size != info.size {
+            return Ok(false);
+        }
+        return Ok(match (&info.etag, &info.last_modified) {
+            (Some(etag), _) if !etag.starts_with("W/") => entry.etag.as_ref() == Some(etag),
+            (_, Some(last_modified)) => entry.last_modified.as_ref() == Some(last_modified),
+            _ => false,
+        });
+    }
+    let metadata = path.metadata()?;
+    if info.size == 0 || metadata.len() != info.size {
+        return Ok(false);
+    }
+    Ok(info
+        .last_modified
+        .as_deref()
+        .and_then(|s| httpdate::parse_http_date(s).ok())
+        .is_some_and(|remote| metadata.modified().is_ok_and(|local| remote <= local)))
+}
+
+/// 把文件的修改时间设置为远端的 Last-Modified
+pub fn set_mtime(path: &Path, last_modified: &str) -> io::Result<()> {
+    let Ok(time) = httpdate::parse_http_date(last_modified) else {
+        return Ok(());
+    };
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(time.max(SystemTime::UNIX_EPOCH))
+}